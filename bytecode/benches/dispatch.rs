@@ -0,0 +1,90 @@
+//! Compares the number of dispatched instructions, and the time taken,
+//! with and without superinstruction fusion.  Run with
+//! `cargo bench --bench dispatch`.
+
+use std::time::Instant;
+
+use bytecode::chunk::Chunk;
+use bytecode::opcode::OpCode;
+use bytecode::value::Value;
+use bytecode::vm::VM;
+
+const ROUNDS: usize = 100_000;
+
+type Build = fn(&mut Chunk);
+
+/// `1 + 1 + 1 + ...`: every add has a constant right operand.
+fn sum(chunk: &mut Chunk) {
+    let one = chunk.add_constant(Value::number(1.0)) as u8;
+    emit_constant(chunk, one);
+    for _ in 0..ROUNDS {
+        emit_constant(chunk, one);
+        chunk.emit_op(OpCode::Add, 1);
+    }
+}
+
+/// `((x * 3 + 2) / 4 - 1) * ...`: a polynomial step per round.
+fn polynomial(chunk: &mut Chunk) {
+    let [one, two, three, four] =
+        [1.0, 2.0, 3.0, 4.0].map(|n| chunk.add_constant(Value::number(n)) as u8);
+    emit_constant(chunk, one);
+    for _ in 0..ROUNDS {
+        emit_constant(chunk, three);
+        chunk.emit_op(OpCode::Multiply, 1);
+        emit_constant(chunk, two);
+        chunk.emit_op(OpCode::Add, 1);
+        emit_constant(chunk, four);
+        chunk.emit_op(OpCode::Divide, 1);
+        emit_constant(chunk, one);
+        chunk.emit_op(OpCode::Subtract, 1);
+    }
+}
+
+/// `-(-(-1 - 1) - 1) ...`: negation in between, so only the
+/// subtractions fuse.
+fn mixed(chunk: &mut Chunk) {
+    let one = chunk.add_constant(Value::number(1.0)) as u8;
+    emit_constant(chunk, one);
+    for _ in 0..ROUNDS {
+        chunk.emit_op(OpCode::Negate, 1);
+        emit_constant(chunk, one);
+        chunk.emit_op(OpCode::Subtract, 1);
+    }
+}
+
+/// Reuse an existing constant slot, since `Chunk::emit_constant`
+/// would add a fresh one each time and run out after 256.
+fn emit_constant(chunk: &mut Chunk, idx: u8) {
+    chunk.emit_op(OpCode::Constant, 1);
+    chunk.emit_byte(idx, 1);
+}
+
+fn measure(name: &str, build: Build, fuse: bool) -> u64 {
+    let mut chunk = Chunk::new(name);
+    chunk.set_fusion(fuse);
+    build(&mut chunk);
+    chunk.emit_op(OpCode::Return, 1);
+
+    let mut vm = VM::new();
+    let start = Instant::now();
+    vm.run(&chunk).expect("benchmark chunk failed");
+    let elapsed = start.elapsed();
+
+    let label = if fuse { "fused" } else { "plain" };
+    println!(
+        "{name:<12} {label:<6} {:>10} dispatches {:>10.3?}",
+        vm.dispatch_count(),
+        elapsed
+    );
+    vm.dispatch_count()
+}
+
+fn main() {
+    let benches: [(&str, Build); 3] = [("sum", sum), ("polynomial", polynomial), ("mixed", mixed)];
+    for (name, build) in benches {
+        let plain = measure(name, build, false);
+        let fused = measure(name, build, true);
+        let saved = 100.0 * (plain - fused) as f64 / plain as f64;
+        println!("{name:<12} {saved:.1}% fewer dispatches\n");
+    }
+}
@@ -0,0 +1,72 @@
+//! Throughput of the dispatch loop on long straight-line chunks.  Run
+//! with `cargo bench --bench run`.
+//!
+//! The VM has no calls or jumps yet, so there is no real fib or loop
+//! to time; the `loop` workload unrolls the body of a counting loop
+//! instead, and `fib` unrolls the additions of an iterative fib.
+
+use std::time::Duration;
+use std::time::Instant;
+
+use bytecode::chunk::Chunk;
+use bytecode::opcode::OpCode;
+use bytecode::value::Value;
+use bytecode::vm::VM;
+
+const ROUNDS: usize = 200_000;
+const REPEAT: usize = 20;
+
+/// `i = i + 1` a few hundred thousand times, plain instructions only.
+fn counting_loop(chunk: &mut Chunk) {
+    chunk.set_fusion(false);
+    let [zero, one] = [0.0, 1.0].map(|n| chunk.add_constant(Value::number(n)) as u8);
+    emit_constant(chunk, zero);
+    for _ in 0..ROUNDS {
+        emit_constant(chunk, one);
+        chunk.emit_op(OpCode::Add, 1);
+    }
+}
+
+/// `a + b` where both come from constants, then folded into a running
+/// total, mimicking the work of iterative fib.
+fn fib(chunk: &mut Chunk) {
+    chunk.set_fusion(false);
+    let [one, two] = [1.0, 2.0].map(|n| chunk.add_constant(Value::number(n)) as u8);
+    emit_constant(chunk, one);
+    for _ in 0..ROUNDS {
+        emit_constant(chunk, one);
+        emit_constant(chunk, two);
+        chunk.emit_op(OpCode::Add, 1);
+        chunk.emit_op(OpCode::Negate, 1);
+        chunk.emit_op(OpCode::Subtract, 1);
+    }
+}
+
+fn emit_constant(chunk: &mut Chunk, idx: u8) {
+    chunk.emit_op(OpCode::Constant, 1);
+    chunk.emit_byte(idx, 1);
+}
+
+fn measure(name: &str, build: fn(&mut Chunk)) {
+    let mut chunk = Chunk::new(name);
+    build(&mut chunk);
+    chunk.emit_op(OpCode::Return, 1);
+
+    let mut best = Duration::MAX;
+    let mut dispatched = 0;
+    for _ in 0..REPEAT {
+        let mut vm = VM::new();
+        let start = Instant::now();
+        vm.run(&chunk).expect("benchmark chunk failed");
+        best = best.min(start.elapsed());
+        dispatched = vm.dispatch_count();
+    }
+
+    let per_op = best.as_nanos() as f64 / dispatched as f64;
+    println!("{name:<8} {dispatched:>10} dispatches {best:>10.3?} {per_op:>6.2} ns/op");
+}
+
+fn main() {
+    measure("loop", counting_loop);
+    measure("fib", fib);
+}
@@ -0,0 +1,9 @@
+//! Debug Lox scripts from an editor: a Debug Adapter Protocol server
+//! on stdin and stdout.
+
+use std::io;
+
+fn main() -> anyhow::Result<()> {
+    bytecode::dap::serve(io::stdin().lock(), io::stdout())?;
+    Ok(())
+}
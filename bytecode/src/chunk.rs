@@ -1,10 +1,20 @@
-use crate::{opcode::OpCode, value::Value};
+use std::io;
+use std::io::Write;
+
+use crate::{
+    error::{LoxError, Result},
+    opcode::OpCode,
+    value::Value,
+};
 
 pub struct Chunk {
     name: String,
     code: Vec<u8>,
     constants: Vec<Value>,
+    names: Vec<String>,
     lines: Vec<(u32, usize)>,
+    last_op: Option<usize>,
+    fuse: bool,
 }
 
 impl Chunk {
@@ -13,14 +23,57 @@ impl Chunk {
             name: name.to_string(),
             code: Vec::new(),
             constants: Vec::new(),
+            names: Vec::new(),
             lines: Vec::new(),
+            last_op: None,
+            fuse: true,
         }
     }
 
+    /// The name the chunk was created with.
+    pub fn function_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Turn peephole fusion of superinstructions on or off for
+    /// everything emitted from now on.  On by default.
+    pub fn set_fusion(&mut self, fuse: bool) {
+        self.fuse = fuse;
+    }
+
+    /// Append `op`, fusing it into the instruction before it when
+    /// fusion is on.  Fusion rewrites that instruction in place, so it
+    /// must never be one a jump lands between: whatever patches a jump
+    /// to land on the next instruction has to set `last_op` to `None`
+    /// first.
     pub fn emit_op(&mut self, op: OpCode, line: u32) {
+        if self.fuse && self.fuse_with_constant(op, line) {
+            return;
+        }
+        self.last_op = Some(self.code.len());
         self.emit_byte(op as u8, line);
     }
 
+    /// If the previous instruction is a `Constant` on the same line,
+    /// rewrite it in place into the superinstruction for `op`.  The
+    /// constant's operand byte stays where it is.
+    fn fuse_with_constant(&mut self, op: OpCode, line: u32) -> bool {
+        let (Some(fused), Some(last)) = (op.with_constant(), self.last_op) else {
+            return false;
+        };
+        let same_line = self.lines.last().is_some_and(|&(l, _)| l == line);
+        if last + 2 != self.code.len() || !same_line {
+            return false;
+        }
+        match OpCode::read(self.code[last]) {
+            OpCode::Constant => {
+                self.code[last] = fused as u8;
+                true
+            }
+            _ => false,
+        }
+    }
+
     pub fn emit_byte(&mut self, byte: u8, line: u32) {
         self.code.push(byte);
         match self.lines.last_mut() {
@@ -37,6 +90,65 @@ impl Chunk {
         self.constants[idx]
     }
 
+    pub fn constants(&self) -> &[Value] {
+        &self.constants
+    }
+
+    pub fn name(&self, idx: usize) -> &str {
+        &self.names[idx]
+    }
+
+    /// The index of a global's name in this chunk's name table, adding
+    /// it if it isn't there yet.
+    pub fn add_name(&mut self, name: &str) -> usize {
+        match self.names.iter().position(|n| n == name) {
+            Some(idx) => idx,
+            None => {
+                self.names.push(name.to_string());
+                self.names.len() - 1
+            }
+        }
+    }
+
+    /// Check that every instruction and its operands lie within the
+    /// code, that constant and name operands are in range, that no
+    /// instruction pops more than is on the stack, and that the chunk
+    /// ends in a `Return`.  The VM relies on this to read the code and
+    /// use the stack without checks.  Returns the most stack slots the
+    /// chunk can use at once.
+    pub fn verify(&self) -> Result<usize> {
+        let mut ip = 0;
+        let mut last = None;
+        let mut depth: usize = 0;
+        let mut max_depth = 0;
+        while ip < self.code.len() {
+            let op = OpCode::try_read(self.code[ip]).ok_or_else(|| invalid(ip))?;
+            let mut operand = 0;
+            if op.operand_bytes() == 1 {
+                operand = *self.code.get(ip + 1).ok_or_else(|| invalid(ip))?;
+            }
+            if (op.takes_constant() && operand as usize >= self.constants.len())
+                || (op.takes_name() && operand as usize >= self.names.len())
+            {
+                return Err(invalid(ip));
+            }
+            let (pops, pushes) = op.stack_effect(operand);
+            depth = depth.checked_sub(pops).ok_or_else(|| invalid(ip))? + pushes;
+            max_depth = max_depth.max(depth);
+            last = Some(op);
+            ip += 1 + op.operand_bytes();
+        }
+        match last {
+            Some(OpCode::Return) => Ok(max_depth),
+            _ => Err(invalid(ip)),
+        }
+    }
+
+    /// The source line of the instruction at byte offset `ip`.
+    pub fn line_at(&self, ip: usize) -> u32 {
+        self.line_bytes().nth(ip).unwrap_or_default()
+    }
+
     pub fn add_constant(&mut self, value: Value) -> usize {
         self.constants.push(value);
         self.constants.len() - 1
@@ -53,64 +165,88 @@ impl Chunk {
     }
 
     pub fn disassemble(&self) {
-        println!("== {} ==", self.name);
+        // there's nowhere to report a failed write to stdout
+        let _ = self.write_disassembly(&mut io::stdout().lock());
+    }
+
+    /// Write a listing of the chunk, one instruction per line.
+    pub fn write_disassembly(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "== {} ==", self.name)?;
         let mut lines = self.line_bytes();
         let mut prev = None;
         let mut ip = 0;
         while ip < self.code.len() {
             let line = lines.next().unwrap();
-            let next = self.disassemble_instruction(ip, line, prev);
+            let next = self.disassemble_instruction(out, ip, line, prev)?;
             for _ in 0..(next - ip - 1) {
                 lines.next();
             }
             prev = Some(line);
             ip = next;
         }
+        Ok(())
+    }
+
+    /// Write the instruction at `ip` as `write_disassembly` would,
+    /// and return the offset of the next one.
+    pub fn write_instruction(&self, out: &mut impl Write, ip: usize) -> io::Result<usize> {
+        self.disassemble_instruction(out, ip, self.line_at(ip), None)
     }
 
-    fn line_bytes(&self) -> impl Iterator<Item = u32> + '_ {
+    /// The source line of every byte of code, in order.
+    pub fn line_bytes(&self) -> impl Iterator<Item = u32> + '_ {
         self.lines
             .iter()
             .flat_map(|&(line, count)| std::iter::repeat_n(line, count))
     }
 
-    fn disassemble_instruction(&self, ip: usize, line: u32, prev: Option<u32>) -> usize {
+    fn disassemble_instruction(
+        &self,
+        out: &mut impl Write,
+        ip: usize,
+        line: u32,
+        prev: Option<u32>,
+    ) -> io::Result<usize> {
         if prev == Some(line) {
-            print!("{ip:04}    | ");
+            write!(out, "{ip:04}    | ")?;
         } else {
-            print!("{ip:04} {line:>4} ");
+            write!(out, "{ip:04} {line:>4} ")?;
         }
         let op = OpCode::read(self.code[ip]);
         match op {
-            OpCode::Constant => {
-                let idx = self.code[ip + 1] as usize;
-                println!("{:<16} {:>4} '{}'", "constant", idx, self.constants[idx]);
-                ip + 2
-            }
-            OpCode::Negate => {
-                println!("negate");
-                ip + 1
-            }
-            OpCode::Add => {
-                println!("add");
-                ip + 1
-            }
-            OpCode::Subtract => {
-                println!("subtract");
-                ip + 1
-            }
-            OpCode::Multiply => {
-                println!("multiply");
-                ip + 1
-            }
-            OpCode::Divide => {
-                println!("divide");
-                ip + 1
-            }
-            OpCode::Return => {
-                println!("return");
-                ip + 1
+            OpCode::Call => {
+                writeln!(out, "{:<16} {:>4}", op.name(), self.code[ip + 1])?;
+                Ok(ip + 2)
             }
+            _ if op.takes_constant() => self.constant_instruction(out, op.name(), ip),
+            _ if op.takes_name() => self.name_instruction(out, op.name(), ip),
+            _ => simple_instruction(out, op.name(), ip),
         }
     }
+
+    fn name_instruction(&self, out: &mut impl Write, name: &str, ip: usize) -> io::Result<usize> {
+        let idx = self.code[ip + 1] as usize;
+        writeln!(out, "{:<16} {:>4} '{}'", name, idx, self.names[idx])?;
+        Ok(ip + 2)
+    }
+
+    fn constant_instruction(
+        &self,
+        out: &mut impl Write,
+        name: &str,
+        ip: usize,
+    ) -> io::Result<usize> {
+        let idx = self.code[ip + 1] as usize;
+        writeln!(out, "{:<16} {:>4} '{}'", name, idx, self.constants[idx])?;
+        Ok(ip + 2)
+    }
+}
+
+fn simple_instruction(out: &mut impl Write, name: &str, ip: usize) -> io::Result<usize> {
+    writeln!(out, "{name}")?;
+    Ok(ip + 1)
+}
+
+fn invalid(ip: usize) -> LoxError {
+    LoxError::CompileError(vec![format!("Invalid bytecode at offset {ip}.")])
 }
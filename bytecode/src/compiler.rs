@@ -1,3 +1,397 @@
-pub fn compile(source: &str) {
-    todo!();
+//! Single-pass compiler from source to a chunk, parsing expressions
+//! with a Pratt parser.
+//!
+//! So far it handles expressions over numbers, booleans and nil,
+//! `print` and expression statements, and global variables.
+
+use crate::{
+    chunk::Chunk,
+    error::{LoxError, Result},
+    opcode::OpCode,
+    scanner::{Lexemes, Scanner},
+    token::Token,
+    value::Value,
+};
+
+/// Compile a whole script.  The chunk returns `nil` when it runs off
+/// the end.
+pub fn compile(source: &str) -> Result<Chunk> {
+    let scanner = Scanner::new(source.to_string());
+    let mut compiler = Compiler::new(scanner.lexemes(), Chunk::new("script"));
+    while !compiler.check(Token::EOF) {
+        compiler.declaration();
+    }
+    // the implicit return belongs to the last line of code, not to
+    // whatever blank lines follow it
+    compiler.finish()
+}
+
+/// Compile a lone expression, with or without a `;` after it, into a
+/// chunk that returns its value, so the REPL can show it.
+pub fn compile_expression(source: &str) -> Result<Chunk> {
+    let scanner = Scanner::new(source.to_string());
+    let mut compiler = Compiler::new(scanner.lexemes(), Chunk::new("script"));
+    compiler.expression();
+    compiler.matches(Token::Semicolon);
+    if !compiler.check(Token::EOF) {
+        compiler.error_at_current("Expect end of expression.");
+    }
+    compiler.end()
+}
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum Precedence {
+    None,
+    Assignment,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    Term,
+    Factor,
+    Unary,
+    Call,
+    Primary,
+}
+
+impl Precedence {
+    fn next(self) -> Self {
+        match self {
+            Precedence::None => Precedence::Assignment,
+            Precedence::Assignment => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Equality,
+            Precedence::Equality => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor => Precedence::Unary,
+            Precedence::Unary => Precedence::Call,
+            Precedence::Call | Precedence::Primary => Precedence::Primary,
+        }
+    }
+
+    /// How tightly the token binds when it appears after an operand.
+    fn of_infix(token: Token) -> Self {
+        match token {
+            Token::LeftParen => Precedence::Call,
+            Token::Star | Token::Slash => Precedence::Factor,
+            Token::Plus | Token::Minus => Precedence::Term,
+            Token::Greater | Token::GreaterEqual | Token::Less | Token::LessEqual => {
+                Precedence::Comparison
+            }
+            Token::EqualEqual | Token::BangEqual => Precedence::Equality,
+            _ => Precedence::None,
+        }
+    }
+}
+
+struct Compiler<'a> {
+    tokens: Lexemes<'a>,
+    current: Token<'a>,
+    current_line: u32,
+    previous: Token<'a>,
+    previous_line: u32,
+    chunk: Chunk,
+    errors: Vec<String>,
+    panic_mode: bool,
+}
+
+impl<'a> Compiler<'a> {
+    fn new(tokens: Lexemes<'a>, chunk: Chunk) -> Self {
+        let mut compiler = Compiler {
+            tokens,
+            current: Token::EOF,
+            current_line: 1,
+            previous: Token::EOF,
+            previous_line: 1,
+            chunk,
+            errors: Vec::new(),
+            panic_mode: false,
+        };
+        compiler.advance();
+        compiler
+    }
+
+    fn finish(mut self) -> Result<Chunk> {
+        self.emit_op(OpCode::Nil);
+        self.end()
+    }
+
+    /// Return whatever is on top of the stack.
+    fn end(mut self) -> Result<Chunk> {
+        self.emit_op(OpCode::Return);
+        if self.errors.is_empty() {
+            Ok(self.chunk)
+        } else {
+            Err(LoxError::CompileError(self.errors))
+        }
+    }
+
+    // token handling
+
+    fn advance(&mut self) {
+        self.previous = self.current;
+        self.previous_line = self.current_line;
+        loop {
+            self.current = self.tokens.next().unwrap_or(Token::EOF);
+            self.current_line = self.tokens.line();
+            match self.current {
+                Token::Error(message) => self.error_at_current(message),
+                _ => break,
+            }
+        }
+    }
+
+    fn check(&self, token: Token) -> bool {
+        self.current == token
+    }
+
+    fn matches(&mut self, token: Token) -> bool {
+        if !self.check(token) {
+            return false;
+        }
+        self.advance();
+        true
+    }
+
+    fn consume(&mut self, token: Token, message: &str) {
+        if self.check(token) {
+            self.advance();
+        } else {
+            self.error_at_current(message);
+        }
+    }
+
+    // errors
+
+    fn error_at_current(&mut self, message: &str) {
+        self.error_at(self.current, self.current_line, message);
+    }
+
+    fn error(&mut self, message: &str) {
+        self.error_at(self.previous, self.previous_line, message);
+    }
+
+    fn error_at(&mut self, token: Token, line: u32, message: &str) {
+        if self.panic_mode {
+            return;
+        }
+        self.panic_mode = true;
+        let location = match token {
+            Token::EOF => " at end".to_string(),
+            Token::Error(_) => String::new(),
+            token => format!(" at '{}'", token.lexeme()),
+        };
+        self.errors
+            .push(format!("[line {line}] Error{location}: {message}"));
+    }
+
+    /// Skip to what looks like the start of the next statement, so one
+    /// mistake doesn't bury the rest of the script in errors.
+    fn synchronize(&mut self) {
+        self.panic_mode = false;
+        while self.current != Token::EOF {
+            if self.previous == Token::Semicolon {
+                return;
+            }
+            match self.current {
+                Token::Class
+                | Token::Fun
+                | Token::Var
+                | Token::For
+                | Token::If
+                | Token::While
+                | Token::Print
+                | Token::Return => return,
+                _ => self.advance(),
+            }
+        }
+    }
+
+    // emitting
+
+    fn emit_op(&mut self, op: OpCode) {
+        self.chunk.emit_op(op, self.previous_line);
+    }
+
+    fn emit_op_with(&mut self, op: OpCode, operand: u8) {
+        self.emit_op(op);
+        self.chunk.emit_byte(operand, self.previous_line);
+    }
+
+    fn emit_constant(&mut self, value: Value) {
+        let idx = self.chunk.add_constant(value);
+        match u8::try_from(idx) {
+            Ok(idx) => self.emit_op_with(OpCode::Constant, idx),
+            Err(_) => self.error("Too many constants in one chunk."),
+        }
+    }
+
+    fn identifier_constant(&mut self, name: &str) -> u8 {
+        let idx = self.chunk.add_name(name);
+        u8::try_from(idx).unwrap_or_else(|_| {
+            self.error("Too many global names in one chunk.");
+            0
+        })
+    }
+
+    // statements
+
+    fn declaration(&mut self) {
+        if self.matches(Token::Var) {
+            self.var_declaration();
+        } else {
+            self.statement();
+        }
+        if self.panic_mode {
+            self.synchronize();
+        }
+    }
+
+    fn var_declaration(&mut self) {
+        let name = match self.current {
+            Token::Identifier(name) => {
+                self.advance();
+                self.identifier_constant(name)
+            }
+            _ => {
+                self.error_at_current("Expect variable name.");
+                return;
+            }
+        };
+        if self.matches(Token::Equal) {
+            self.expression();
+        } else {
+            self.emit_op(OpCode::Nil);
+        }
+        self.consume(Token::Semicolon, "Expect ';' after variable declaration.");
+        self.emit_op_with(OpCode::DefineGlobal, name);
+    }
+
+    fn statement(&mut self) {
+        if self.matches(Token::Print) {
+            self.expression();
+            self.consume(Token::Semicolon, "Expect ';' after value.");
+            self.emit_op(OpCode::Print);
+        } else {
+            self.expression();
+            self.consume(Token::Semicolon, "Expect ';' after expression.");
+            self.emit_op(OpCode::Pop);
+        }
+    }
+
+    // expressions
+
+    fn expression(&mut self) {
+        self.parse_precedence(Precedence::Assignment);
+    }
+
+    fn parse_precedence(&mut self, precedence: Precedence) {
+        self.advance();
+        let can_assign = precedence <= Precedence::Assignment;
+        if !self.prefix(can_assign) {
+            self.error("Expect expression.");
+            return;
+        }
+        while precedence <= Precedence::of_infix(self.current) {
+            self.advance();
+            self.infix();
+        }
+        if can_assign && self.matches(Token::Equal) {
+            self.error("Invalid assignment target.");
+        }
+    }
+
+    /// Compile the operand that starts with the previous token, or
+    /// return false if nothing can start with it.
+    fn prefix(&mut self, can_assign: bool) -> bool {
+        match self.previous {
+            Token::Number(text) => match text.parse() {
+                Ok(n) => self.emit_constant(Value::number(n)),
+                Err(_) => self.error("Invalid number."),
+            },
+            Token::String(_) => self.error("Strings are not supported yet."),
+            Token::Nil => self.emit_op(OpCode::Nil),
+            Token::True => self.emit_op(OpCode::True),
+            Token::False => self.emit_op(OpCode::False),
+            Token::Identifier(name) => self.variable(name, can_assign),
+            Token::LeftParen => {
+                self.expression();
+                self.consume(Token::RightParen, "Expect ')' after expression.");
+            }
+            Token::Minus | Token::Bang => {
+                let operator = self.previous;
+                self.parse_precedence(Precedence::Unary);
+                match operator {
+                    Token::Minus => self.emit_op(OpCode::Negate),
+                    _ => self.emit_op(OpCode::Not),
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Compile the rest of an infix expression whose operator is the
+    /// previous token, with its left operand already on the stack.
+    fn infix(&mut self) {
+        let operator = self.previous;
+        if operator == Token::LeftParen {
+            let argc = self.argument_list();
+            self.emit_op_with(OpCode::Call, argc);
+            return;
+        }
+
+        self.parse_precedence(Precedence::of_infix(operator).next());
+        match operator {
+            Token::Plus => self.emit_op(OpCode::Add),
+            Token::Minus => self.emit_op(OpCode::Subtract),
+            Token::Star => self.emit_op(OpCode::Multiply),
+            Token::Slash => self.emit_op(OpCode::Divide),
+            Token::EqualEqual => self.emit_op(OpCode::Equal),
+            Token::BangEqual => {
+                self.emit_op(OpCode::Equal);
+                self.emit_op(OpCode::Not);
+            }
+            Token::Greater => self.emit_op(OpCode::Greater),
+            Token::GreaterEqual => {
+                self.emit_op(OpCode::Less);
+                self.emit_op(OpCode::Not);
+            }
+            Token::Less => self.emit_op(OpCode::Less),
+            Token::LessEqual => {
+                self.emit_op(OpCode::Greater);
+                self.emit_op(OpCode::Not);
+            }
+            _ => unreachable!("no infix rule for {operator:?}"),
+        }
+    }
+
+    fn variable(&mut self, name: &str, can_assign: bool) {
+        let idx = self.identifier_constant(name);
+        if can_assign && self.matches(Token::Equal) {
+            self.expression();
+            self.emit_op_with(OpCode::SetGlobal, idx);
+        } else {
+            self.emit_op_with(OpCode::GetGlobal, idx);
+        }
+    }
+
+    fn argument_list(&mut self) -> u8 {
+        let mut argc: usize = 0;
+        if !self.check(Token::RightParen) {
+            loop {
+                self.expression();
+                if argc == 255 {
+                    self.error("Can't have more than 255 arguments.");
+                }
+                argc += 1;
+                if !self.matches(Token::Comma) {
+                    break;
+                }
+            }
+        }
+        self.consume(Token::RightParen, "Expect ')' after arguments.");
+        argc.min(255) as u8
+    }
 }
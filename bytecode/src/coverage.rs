@@ -0,0 +1,102 @@
+//! Which source lines of a script ran, and how often.  A VM gathers
+//! this while coverage is switched on with `VM::start_coverage`.
+//!
+//! Lines are known to hold code once a chunk with code on them starts
+//! running, so a line counts as missed when its chunk ran but it
+//! didn't.  Line numbers are all that identifies a line, so one
+//! `Coverage` should only see chunks compiled from one source.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::io::Write;
+
+use crate::chunk::Chunk;
+
+#[derive(Debug, Default)]
+pub struct Coverage {
+    /// How often each line that holds code ran.
+    lines: BTreeMap<u32, u64>,
+    /// Executions of each instruction of the chunks running now, by
+    /// offset in the code.
+    frames: Vec<Vec<u64>>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn enter(&mut self, chunk: &Chunk) {
+        // line 0 is code the host built, which has no source
+        for line in chunk.line_bytes().filter(|&line| line > 0) {
+            self.lines.entry(line).or_default();
+        }
+        self.frames.push(vec![0; chunk.code().len()]);
+    }
+
+    #[inline]
+    pub(crate) fn count(&mut self, offset: usize) {
+        if let Some(counts) = self.frames.last_mut() {
+            counts[offset] += 1;
+        }
+    }
+
+    /// Leave the chunk entered last, which must be `chunk`.
+    pub(crate) fn exit(&mut self, chunk: &Chunk) {
+        let Some(counts) = self.frames.pop() else {
+            return;
+        };
+        // a line ran as often as its busiest instruction
+        let mut runs = BTreeMap::new();
+        for (line, count) in chunk.line_bytes().zip(counts) {
+            let runs = runs.entry(line).or_default();
+            *runs = count.max(*runs);
+        }
+        for (line, count) in runs {
+            if let Some(hits) = self.lines.get_mut(&line) {
+                *hits += count;
+            }
+        }
+    }
+
+    /// How often `line` ran, or `None` if it holds no code.
+    pub fn hits(&self, line: u32) -> Option<u64> {
+        self.lines.get(&line).copied()
+    }
+
+    /// Lines that hold code.
+    pub fn lines_found(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Lines that hold code and ran.
+    pub fn lines_hit(&self) -> usize {
+        self.lines.values().filter(|&&hits| hits > 0).count()
+    }
+
+    /// Write an lcov tracefile naming the script `source_file`.
+    pub fn write_lcov(&self, out: &mut impl Write, source_file: &str) -> io::Result<()> {
+        writeln!(out, "TN:")?;
+        writeln!(out, "SF:{source_file}")?;
+        for (line, hits) in &self.lines {
+            writeln!(out, "DA:{line},{hits}")?;
+        }
+        writeln!(out, "LF:{}", self.lines_found())?;
+        writeln!(out, "LH:{}", self.lines_hit())?;
+        writeln!(out, "end_of_record")
+    }
+
+    /// Write `source` with each line prefixed by how often it ran, the
+    /// way gcov does: `-` for lines without code and `#####` for lines
+    /// that never ran.
+    pub fn write_annotated(&self, out: &mut impl Write, source: &str) -> io::Result<()> {
+        for (number, text) in (1..).zip(source.lines()) {
+            match self.hits(number) {
+                None => writeln!(out, "{:>9}:{number:>5}: {text}", "-")?,
+                Some(0) => writeln!(out, "{:>9}:{number:>5}: {text}", "#####")?,
+                Some(hits) => writeln!(out, "{hits:>9}:{number:>5}: {text}")?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,414 @@
+//! A Debug Adapter Protocol server, so editors can debug Lox scripts.
+//!
+//! It speaks DAP over a pair of streams, normally stdin and stdout,
+//! and debugs one script with a `Debugger` whose frontend turns pauses
+//! into `stopped` events and answers requests until told to go on.
+//! There is only ever one thread, and the only scope is the globals.
+
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::io::LineWriter;
+use std::io::Write;
+use std::rc::Rc;
+
+use serde_json::Value as Json;
+use serde_json::json;
+
+use crate::{
+    debugger::{Breakpoint, Debugger, Frontend, PauseReason, Paused, Resume},
+    error::LoxError,
+    lox::Lox,
+};
+
+const THREAD_ID: i64 = 1;
+const GLOBALS_REFERENCE: i64 = 1;
+
+/// Serve one debug session, until the client disconnects or closes
+/// `input`.
+pub fn serve(input: impl BufRead + 'static, output: impl Write + 'static) -> io::Result<()> {
+    let transport = Rc::new(RefCell::new(Transport {
+        input: Box::new(input),
+        output: Box::new(output),
+        seq: 0,
+    }));
+    Server {
+        transport,
+        breakpoints: Vec::new(),
+        launch: None,
+        configured: false,
+        ran: false,
+    }
+    .run()
+}
+
+/// Messages framed with a `Content-Length` header.
+struct Transport {
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    seq: i64,
+}
+
+impl Transport {
+    /// The next message, or `None` at the end of the input.
+    fn read(&mut self) -> io::Result<Option<Json>> {
+        let mut length = None;
+        loop {
+            let mut header = String::new();
+            if self.input.read_line(&mut header)? == 0 {
+                return Ok(None);
+            }
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(value) = header.strip_prefix("Content-Length:") {
+                length = value.trim().parse().ok();
+            }
+        }
+        let Some(length) = length else {
+            return Err(invalid_data("missing Content-Length"));
+        };
+        let mut body = vec![0; length];
+        self.input.read_exact(&mut body)?;
+        serde_json::from_slice(&body)
+            .map(Some)
+            .map_err(|e| invalid_data(e.to_string()))
+    }
+
+    fn send(&mut self, mut message: Json) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        let body = message.to_string();
+        write!(self.output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+        self.output.flush()
+    }
+
+    fn respond(&mut self, request: &Json, body: Json) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": true,
+            "body": body,
+        }))
+    }
+
+    fn respond_error(&mut self, request: &Json, message: &str) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": false,
+            "message": message,
+        }))
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// What the client asked us to run.
+struct Launch {
+    path: String,
+    source: String,
+    stop_on_entry: bool,
+}
+
+/// Handles requests until the script can run, runs it, and handles
+/// whatever comes after.
+struct Server {
+    transport: Rc<RefCell<Transport>>,
+    breakpoints: Vec<Breakpoint>,
+    launch: Option<Launch>,
+    configured: bool,
+    ran: bool,
+}
+
+impl Server {
+    fn run(mut self) -> io::Result<()> {
+        loop {
+            let Some(request) = self.transport.borrow_mut().read()? else {
+                return Ok(());
+            };
+            if !self.handle(&request)? {
+                return Ok(());
+            }
+            if self.configured
+                && !self.ran
+                && let Some(launch) = self.launch.take()
+            {
+                self.ran = true;
+                if !self.debug(launch)? {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Answer a request made while the script isn't running.  Returns
+    /// false once the client has disconnected.
+    fn handle(&mut self, request: &Json) -> io::Result<bool> {
+        let mut transport = self.transport.borrow_mut();
+        match request["command"].as_str().unwrap_or_default() {
+            "initialize" => {
+                transport.respond(request, json!({ "supportsConfigurationDoneRequest": true }))?;
+                transport.event("initialized", json!({}))?;
+            }
+            "launch" => {
+                let arguments = &request["arguments"];
+                let Some(path) = arguments["program"].as_str() else {
+                    transport.respond_error(request, "Missing 'program'.")?;
+                    return Ok(true);
+                };
+                match fs::read_to_string(path) {
+                    Ok(source) => {
+                        self.launch = Some(Launch {
+                            path: path.to_string(),
+                            source,
+                            stop_on_entry: arguments["stopOnEntry"].as_bool().unwrap_or(false),
+                        });
+                        transport.respond(request, json!({}))?;
+                    }
+                    Err(e) => {
+                        transport.respond_error(request, &format!("Can't read {path}: {e}."))?
+                    }
+                }
+            }
+            "setBreakpoints" => {
+                let body = set_breakpoints(&mut self.breakpoints, request);
+                transport.respond(request, body)?;
+            }
+            "configurationDone" => {
+                self.configured = true;
+                transport.respond(request, json!({}))?;
+            }
+            "threads" => transport.respond(request, threads())?,
+            "disconnect" => {
+                transport.respond(request, json!({}))?;
+                return Ok(false);
+            }
+            "stackTrace" | "scopes" | "variables" | "continue" | "next" | "stepIn" | "stepOut" => {
+                transport.respond_error(request, "The script isn't paused.")?
+            }
+            command => transport.respond_error(request, &format!("Unsupported: {command}."))?,
+        }
+        Ok(true)
+    }
+
+    /// Run the script under a debugger.  Returns false if the client
+    /// disconnected while it ran.
+    fn debug(&mut self, launch: Launch) -> io::Result<bool> {
+        let disconnected = Rc::new(Cell::new(false));
+        let frontend = DapFrontend {
+            transport: Rc::clone(&self.transport),
+            path: launch.path,
+            disconnected: Rc::clone(&disconnected),
+        };
+        let mut debugger = Debugger::new(Box::new(frontend));
+        debugger.set_stop_on_entry(launch.stop_on_entry);
+        *debugger.breakpoints_mut() = self.breakpoints.clone();
+
+        let mut lox = Lox::new();
+        // one event per line, not per write
+        lox.set_output(LineWriter::new(OutputEvents(Rc::clone(&self.transport))));
+        lox.vm().attach_debugger(debugger);
+        let result = lox.run(&launch.source);
+        // flush the last line
+        drop(lox);
+        if disconnected.get() {
+            return Ok(false);
+        }
+
+        let mut transport = self.transport.borrow_mut();
+        let exit_code = match result {
+            Ok(()) => 0,
+            Err(e) => {
+                transport.event(
+                    "output",
+                    json!({ "category": "stderr", "output": format!("{e}\n") }),
+                )?;
+                match e {
+                    LoxError::CompileError(_) => 65,
+                    _ => 70,
+                }
+            }
+        };
+        transport.event("exited", json!({ "exitCode": exit_code }))?;
+        transport.event("terminated", json!({}))?;
+        Ok(true)
+    }
+}
+
+fn threads() -> Json {
+    json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })
+}
+
+/// Replace the line breakpoints with those in the request, which are
+/// all for the one script we debug.
+fn set_breakpoints(breakpoints: &mut Vec<Breakpoint>, request: &Json) -> Json {
+    breakpoints.retain(|b| !matches!(b, Breakpoint::Line(_)));
+    let lines: Vec<u32> = request["arguments"]["breakpoints"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|b| b["line"].as_u64())
+        .map(|line| line as u32)
+        .collect();
+    breakpoints.extend(lines.iter().map(|&line| Breakpoint::Line(line)));
+    let verified: Vec<_> = lines
+        .iter()
+        .map(|line| json!({ "verified": true, "line": line }))
+        .collect();
+    json!({ "breakpoints": verified })
+}
+
+/// Sends what the script prints to the client as `output` events.
+struct OutputEvents(Rc<RefCell<Transport>>);
+
+impl Write for OutputEvents {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let output = String::from_utf8_lossy(buf);
+        self.0
+            .borrow_mut()
+            .event("output", json!({ "category": "stdout", "output": output }))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct DapFrontend {
+    transport: Rc<RefCell<Transport>>,
+    path: String,
+    disconnected: Rc<Cell<bool>>,
+}
+
+impl DapFrontend {
+    fn serve_pause(&mut self, paused: &mut Paused) -> io::Result<Resume> {
+        let mut transport = self.transport.borrow_mut();
+        let reason = match paused.reason() {
+            PauseReason::Entry => "entry",
+            PauseReason::Step => "step",
+            PauseReason::Breakpoint => "breakpoint",
+        };
+        transport.event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        )?;
+
+        loop {
+            let Some(request) = transport.read()? else {
+                self.disconnected.set(true);
+                return Ok(Resume::Quit);
+            };
+            let resume = match request["command"].as_str().unwrap_or_default() {
+                "continue" => Resume::Continue,
+                "next" => Resume::StepOver,
+                "stepIn" => Resume::StepInto,
+                "stepOut" => Resume::StepOut,
+                "disconnect" => {
+                    transport.respond(&request, json!({}))?;
+                    self.disconnected.set(true);
+                    return Ok(Resume::Quit);
+                }
+                "threads" => {
+                    transport.respond(&request, threads())?;
+                    continue;
+                }
+                "stackTrace" => {
+                    transport.respond(&request, self.stack_trace(paused))?;
+                    continue;
+                }
+                "scopes" => {
+                    let scope = json!({
+                        "name": "Globals",
+                        "variablesReference": GLOBALS_REFERENCE,
+                        "expensive": false,
+                    });
+                    transport.respond(&request, json!({ "scopes": [scope] }))?;
+                    continue;
+                }
+                "variables" => {
+                    let variables: Vec<_> =
+                        match request["arguments"]["variablesReference"].as_i64() {
+                            Some(GLOBALS_REFERENCE) => paused
+                                .globals()
+                                .into_iter()
+                                .map(|(name, value)| {
+                                    json!({
+                                        "name": name,
+                                        "value": value.to_string(),
+                                        "variablesReference": 0,
+                                    })
+                                })
+                                .collect(),
+                            _ => Vec::new(),
+                        };
+                    transport.respond(&request, json!({ "variables": variables }))?;
+                    continue;
+                }
+                "setBreakpoints" => {
+                    let body = set_breakpoints(paused.breakpoints(), &request);
+                    transport.respond(&request, body)?;
+                    continue;
+                }
+                command => {
+                    transport.respond_error(&request, &format!("Unsupported: {command}."))?;
+                    continue;
+                }
+            };
+            let body = match resume {
+                Resume::Continue => json!({ "allThreadsContinued": true }),
+                _ => json!({}),
+            };
+            transport.respond(&request, body)?;
+            return Ok(resume);
+        }
+    }
+
+    fn stack_trace(&self, paused: &Paused) -> Json {
+        let frames: Vec<_> = paused
+            .backtrace()
+            .into_iter()
+            .enumerate()
+            .map(|(id, (name, line))| match line {
+                Some(line) => json!({
+                    "id": id,
+                    "name": name,
+                    "source": { "path": self.path },
+                    "line": line,
+                    "column": 1,
+                }),
+                // natives have no source
+                None => json!({
+                    "id": id,
+                    "name": name,
+                    "line": 0,
+                    "column": 0,
+                    "presentationHint": "subtle",
+                }),
+            })
+            .collect();
+        json!({ "stackFrames": frames, "totalFrames": frames.len() })
+    }
+}
+
+impl Frontend for DapFrontend {
+    fn paused(&mut self, paused: &mut Paused) -> Resume {
+        // a client we can't talk to can't debug anything
+        self.serve_pause(paused).unwrap_or_else(|_| {
+            self.disconnected.set(true);
+            Resume::Quit
+        })
+    }
+}
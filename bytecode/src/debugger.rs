@@ -0,0 +1,428 @@
+//! Breakpoints and stepping for scripts running in the VM.
+//!
+//! A `Debugger` attached with `VM::attach_debugger` follows the chunks
+//! and natives the VM enters, and decides when to pause.  What happens
+//! while paused is up to its `Frontend`, which can look at the script
+//! through `Paused` and then says how to go on.  `Console` is a
+//! frontend that reads commands from a terminal.
+
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+
+use crate::{
+    chunk::Chunk,
+    error::{LoxError, Result},
+    value::Value,
+    vm::VM,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Breakpoint {
+    Line(u32),
+    /// Stops on entering any chunk or native with this name.
+    Function(String),
+}
+
+impl Breakpoint {
+    /// A line number, or else a function name.
+    pub fn parse(text: &str) -> Self {
+        match text.parse() {
+            Ok(line) => Breakpoint::Line(line),
+            Err(_) => Breakpoint::Function(text.to_string()),
+        }
+    }
+}
+
+/// How to go on after a pause.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume {
+    /// Run to the next breakpoint.
+    Continue,
+    /// Stop at the next line, even in a function called from this one.
+    StepInto,
+    /// Stop at the next line of this function or its callers.
+    StepOver,
+    /// Stop at the next line of a caller.
+    StepOut,
+    /// Stop the script with `LoxError::Interrupted`.
+    Quit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseReason {
+    Entry,
+    Step,
+    Breakpoint,
+}
+
+pub trait Frontend {
+    /// The script is paused.  Look at it through `paused`, and say how
+    /// to go on.
+    fn paused(&mut self, paused: &mut Paused) -> Resume;
+}
+
+/// A chunk or native the VM is running.
+#[derive(Debug)]
+struct Frame {
+    name: String,
+    /// The line of each byte of code; empty for a native.
+    lines: Vec<u32>,
+    /// The line of the last instruction run.
+    line: Option<u32>,
+    /// Entered through a function breakpoint, so stop at its first
+    /// line.
+    break_on_line: bool,
+}
+
+pub struct Debugger {
+    frontend: Box<dyn Frontend>,
+    breakpoints: Vec<Breakpoint>,
+    resume: Resume,
+    /// How many frames deep we were when told to step.
+    step_depth: usize,
+    stop_on_entry: bool,
+    frames: Vec<Frame>,
+}
+
+impl Debugger {
+    /// A debugger that pauses before the first line of the script.
+    pub fn new(frontend: Box<dyn Frontend>) -> Self {
+        Debugger {
+            frontend,
+            breakpoints: Vec::new(),
+            resume: Resume::StepInto,
+            step_depth: 0,
+            stop_on_entry: true,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Whether to pause before the first line, or run to the first
+    /// breakpoint.
+    pub fn set_stop_on_entry(&mut self, stop: bool) {
+        self.stop_on_entry = stop;
+        self.resume = if stop {
+            Resume::StepInto
+        } else {
+            Resume::Continue
+        };
+    }
+
+    pub fn breakpoints_mut(&mut self) -> &mut Vec<Breakpoint> {
+        &mut self.breakpoints
+    }
+
+    pub(crate) fn enter_chunk(&mut self, chunk: &Chunk) {
+        let name = chunk.function_name();
+        self.frames.push(Frame {
+            name: name.to_string(),
+            lines: chunk.line_bytes().collect(),
+            line: None,
+            break_on_line: self.breaks_on_function(name),
+        });
+    }
+
+    /// Natives have no lines to stop at, so a breakpoint on one stops
+    /// as it is called.
+    pub(crate) fn enter_native(&mut self, vm: &VM, name: &str) -> Result<()> {
+        self.frames.push(Frame {
+            name: name.to_string(),
+            lines: Vec::new(),
+            line: None,
+            break_on_line: false,
+        });
+        if self.breaks_on_function(name) {
+            self.pause(vm, PauseReason::Breakpoint)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn exit(&mut self) {
+        self.frames.pop();
+    }
+
+    /// Called before the instruction at `offset` in the innermost
+    /// chunk runs, and pauses if it starts a line we should stop at.
+    pub(crate) fn at(&mut self, vm: &VM, offset: usize) -> Result<()> {
+        let depth = self.frames.len();
+        let Some(frame) = self.frames.last_mut() else {
+            return Ok(());
+        };
+        let line = frame.lines[offset];
+        // line 0 is code the host built
+        if line == 0 || frame.line == Some(line) {
+            return Ok(());
+        }
+        frame.line = Some(line);
+
+        let on_function = std::mem::take(&mut frame.break_on_line);
+        let reason = if on_function || self.breakpoints.contains(&Breakpoint::Line(line)) {
+            PauseReason::Breakpoint
+        } else {
+            match self.resume {
+                Resume::StepInto if self.stop_on_entry => PauseReason::Entry,
+                Resume::StepInto => PauseReason::Step,
+                Resume::StepOver if depth <= self.step_depth => PauseReason::Step,
+                Resume::StepOut if depth < self.step_depth => PauseReason::Step,
+                _ => return Ok(()),
+            }
+        };
+        self.pause(vm, reason)
+    }
+
+    fn breaks_on_function(&self, name: &str) -> bool {
+        self.breakpoints
+            .iter()
+            .any(|b| matches!(b, Breakpoint::Function(f) if f == name))
+    }
+
+    fn pause(&mut self, vm: &VM, reason: PauseReason) -> Result<()> {
+        self.stop_on_entry = false;
+        let mut paused = Paused {
+            vm,
+            frames: &self.frames,
+            breakpoints: &mut self.breakpoints,
+            reason,
+        };
+        self.resume = self.frontend.paused(&mut paused);
+        self.step_depth = self.frames.len();
+        match self.resume {
+            Resume::Quit => Err(LoxError::Interrupted { line: 0 }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A view of the script while it is paused.
+pub struct Paused<'a> {
+    vm: &'a VM,
+    frames: &'a [Frame],
+    breakpoints: &'a mut Vec<Breakpoint>,
+    reason: PauseReason,
+}
+
+impl Paused<'_> {
+    pub fn reason(&self) -> PauseReason {
+        self.reason
+    }
+
+    /// The function we stopped in.
+    pub fn function(&self) -> &str {
+        self.frames.last().map_or("script", |f| f.name.as_str())
+    }
+
+    /// The line we stopped on.  In a native, the line it was called
+    /// from.
+    pub fn line(&self) -> Option<u32> {
+        self.frames.iter().rev().find_map(|f| f.line)
+    }
+
+    /// Each function running, innermost first, with the line it is on.
+    /// Natives have no line.
+    pub fn backtrace(&self) -> Vec<(&str, Option<u32>)> {
+        self.frames
+            .iter()
+            .rev()
+            .map(|f| (f.name.as_str(), f.line))
+            .collect()
+    }
+
+    pub fn global(&self, name: &str) -> Option<Value> {
+        self.vm.global(name)
+    }
+
+    // TODO: locals and upvalues by name, once the compiler has scopes
+    // and closures and keeps their names in the function's metadata
+
+    /// Every global but the natives, by name.
+    pub fn globals(&self) -> Vec<(&str, Value)> {
+        let mut globals: Vec<_> = self
+            .vm
+            .globals()
+            .filter(|(_, value)| value.as_native().is_none())
+            .collect();
+        globals.sort_by_key(|&(name, _)| name);
+        globals
+    }
+
+    /// The VM's value stack, bottom first.
+    pub fn stack(&self) -> &[Value] {
+        self.vm.stack()
+    }
+
+    pub fn breakpoints(&mut self) -> &mut Vec<Breakpoint> {
+        self.breakpoints
+    }
+}
+
+/// A frontend that shows where the script stopped and takes commands
+/// much like gdb's.  Reaching the end of the input lets the script run
+/// to the end.
+pub struct Console<R, W> {
+    input: R,
+    out: W,
+    source: Vec<String>,
+}
+
+const HELP: &str = "\
+break LINE|FUNCTION   stop at a line, or on entering a function (b)
+delete LINE|FUNCTION  remove a breakpoint
+breakpoints           list breakpoints
+continue              run to the next breakpoint (c)
+step                  run to the next line, into calls (s)
+next                  run to the next line, over calls (n)
+finish                run until this function returns
+print NAME            show a variable (p)
+globals               show every global
+stack                 show the value stack
+backtrace             show the functions running (bt)
+list                  show the source around this line (l)
+quit                  stop the script (q)";
+
+impl<R: BufRead, W: Write> Console<R, W> {
+    /// `source` is the script, for showing the lines we stop on.
+    pub fn new(input: R, out: W, source: &str) -> Self {
+        Console {
+            input,
+            out,
+            source: source.lines().map(str::to_string).collect(),
+        }
+    }
+
+    fn show_line(&mut self, line: u32) -> io::Result<()> {
+        let text = self
+            .source
+            .get(line as usize - 1)
+            .map_or("", String::as_str);
+        writeln!(self.out, "{line:>5} | {text}")
+    }
+
+    fn list(&mut self, around: u32) -> io::Result<()> {
+        let first = around.saturating_sub(3).max(1);
+        let last = (around + 3).min(self.source.len() as u32);
+        for line in first..=last {
+            let marker = if line == around { '>' } else { ' ' };
+            let text = &self.source[line as usize - 1];
+            writeln!(self.out, "{marker}{line:>4} | {text}")?;
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, paused: &mut Paused, line: &str) -> io::Result<Option<Resume>> {
+        let (command, arg) = match line.split_once(' ') {
+            Some((command, arg)) => (command, arg.trim()),
+            None => (line, ""),
+        };
+        let resume = match (command, arg) {
+            ("c" | "continue", "") => Resume::Continue,
+            ("s" | "step", "") => Resume::StepInto,
+            ("n" | "next", "") => Resume::StepOver,
+            ("finish", "") => Resume::StepOut,
+            ("q" | "quit", "") => Resume::Quit,
+            ("b" | "break", arg) if !arg.is_empty() => {
+                let breakpoint = Breakpoint::parse(arg);
+                if !paused.breakpoints().contains(&breakpoint) {
+                    paused.breakpoints().push(breakpoint);
+                }
+                writeln!(self.out, "Breakpoint at {arg}.")?;
+                return Ok(None);
+            }
+            ("delete", arg) if !arg.is_empty() => {
+                let breakpoint = Breakpoint::parse(arg);
+                let breakpoints = paused.breakpoints();
+                let before = breakpoints.len();
+                breakpoints.retain(|b| *b != breakpoint);
+                if breakpoints.len() == before {
+                    writeln!(self.out, "No breakpoint at {arg}.")?;
+                }
+                return Ok(None);
+            }
+            ("breakpoints", "") => {
+                for breakpoint in paused.breakpoints().clone() {
+                    match breakpoint {
+                        Breakpoint::Line(line) => writeln!(self.out, "line {line}")?,
+                        Breakpoint::Function(name) => writeln!(self.out, "function {name}")?,
+                    }
+                }
+                return Ok(None);
+            }
+            ("p" | "print", name) if !name.is_empty() => {
+                match paused.global(name) {
+                    Some(value) => writeln!(self.out, "{name} = {value}")?,
+                    None => writeln!(self.out, "No variable '{name}'.")?,
+                }
+                return Ok(None);
+            }
+            ("globals", "") => {
+                for (name, value) in paused.globals() {
+                    writeln!(self.out, "{name} = {value}")?;
+                }
+                return Ok(None);
+            }
+            ("stack", "") => {
+                writeln!(self.out, "{:?}", paused.stack())?;
+                return Ok(None);
+            }
+            ("bt" | "backtrace", "") => {
+                for (depth, (name, line)) in paused.backtrace().into_iter().enumerate() {
+                    match line {
+                        Some(line) => writeln!(self.out, "#{depth} {name}, line {line}")?,
+                        None => writeln!(self.out, "#{depth} {name}")?,
+                    }
+                }
+                return Ok(None);
+            }
+            ("l" | "list", "") => {
+                if let Some(line) = paused.line() {
+                    self.list(line)?;
+                }
+                return Ok(None);
+            }
+            ("h" | "help", "") => {
+                writeln!(self.out, "{HELP}")?;
+                return Ok(None);
+            }
+            _ => {
+                writeln!(self.out, "Unknown command '{line}'; try 'help'.")?;
+                return Ok(None);
+            }
+        };
+        Ok(Some(resume))
+    }
+
+    fn prompt(&mut self, paused: &mut Paused) -> io::Result<Resume> {
+        let reason = match paused.reason() {
+            PauseReason::Entry => "Stopped at entry",
+            PauseReason::Step => "Stopped",
+            PauseReason::Breakpoint => "Breakpoint",
+        };
+        writeln!(self.out, "{reason} in {}", paused.function())?;
+        if let Some(line) = paused.line() {
+            self.show_line(line)?;
+        }
+
+        loop {
+            write!(self.out, "(lox) ")?;
+            self.out.flush()?;
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                return Ok(Resume::Continue);
+            }
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(resume) = self.command(paused, line)? {
+                return Ok(resume);
+            }
+        }
+    }
+}
+
+impl<R: BufRead, W: Write> Frontend for Console<R, W> {
+    fn paused(&mut self, paused: &mut Paused) -> Resume {
+        // if we can't talk to the user there's no point stopping
+        self.prompt(paused).unwrap_or(Resume::Continue)
+    }
+}
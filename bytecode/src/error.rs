@@ -1,21 +1,53 @@
 use std::error::Error;
 use std::fmt;
 
+use crate::limits::Limit;
+
 pub type Result<T> = std::result::Result<T, LoxError>;
 
 #[derive(Debug)]
 pub enum LoxError {
-    CompileError,
-    RuntimeError,
+    /// One message per error, already formatted with its line.
+    CompileError(Vec<String>),
+    RuntimeError { message: String, line: u32 },
+    /// The script hit one of the VM's `Limits`.
+    LimitExceeded { limit: Limit, line: u32 },
+    /// The host raised the VM's interrupt flag.
+    Interrupted { line: u32 },
+}
+
+impl LoxError {
+    /// A runtime error whose line isn't known yet; the VM fills it in
+    /// with `at_line` once it knows which instruction failed.
+    pub fn runtime(message: impl Into<String>) -> Self {
+        LoxError::RuntimeError {
+            message: message.into(),
+            line: 0,
+        }
+    }
+
+    pub fn at_line(self, line: u32) -> Self {
+        match self {
+            LoxError::RuntimeError { message, .. } => LoxError::RuntimeError { message, line },
+            LoxError::LimitExceeded { limit, .. } => LoxError::LimitExceeded { limit, line },
+            LoxError::Interrupted { .. } => LoxError::Interrupted { line },
+            e => e,
+        }
+    }
 }
 
 impl fmt::Display for LoxError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let out = match self {
-            LoxError::CompileError => "Compile Error",
-            LoxError::RuntimeError => "Runtime Error",
-        };
-        write!(f, "{out}")
+        match self {
+            LoxError::CompileError(errors) => write!(f, "{}", errors.join("\n")),
+            LoxError::RuntimeError { message, line } => {
+                write!(f, "{message}\n[line {line}] in script")
+            }
+            LoxError::LimitExceeded { limit, line } => {
+                write!(f, "{limit}\n[line {line}] in script")
+            }
+            LoxError::Interrupted { line } => write!(f, "Interrupted.\n[line {line}] in script"),
+        }
     }
 }
 
@@ -1,49 +1,200 @@
 pub mod chunk;
 pub mod compiler;
+pub mod coverage;
+pub mod dap;
 pub mod debug;
+pub mod debugger;
 pub mod error;
+pub mod limits;
+pub mod lox;
+pub mod native;
 pub mod opcode;
+pub mod profile;
+pub mod repl;
 pub mod scanner;
+pub mod stack;
 pub mod token;
 pub mod value;
 pub mod vm;
 
+use std::env;
+use std::fs::File;
+use std::io;
 use std::io::Read;
+use std::io::Write;
+use std::path::PathBuf;
 
-use log::{Level, log_enabled};
+use rustyline::DefaultEditor;
+use rustyline::error::ReadlineError;
 
-use crate::{chunk::Chunk, opcode::OpCode, scanner::Scanner, vm::VM};
+use crate::{
+    compiler::{compile, compile_expression},
+    debugger::{Console, Debugger},
+    lox::Lox,
+    repl::{Prompt, Repl},
+    scanner::Scanner,
+};
 
-pub fn run_from_source(mut reader: impl Read) -> anyhow::Result<()> {
+/// What to do with a script.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    #[default]
+    Run,
+    /// Print the tokens the scanner finds, one per line.
+    Tokens,
+    /// Print the compiled chunk's disassembly.
+    Disassemble,
+    /// Compile, and report any errors.
+    Check,
+}
+
+/// How to handle a script.  Everything but `mode` and `echo` only
+/// applies when running it.
+#[derive(Debug, Default)]
+pub struct RunOptions {
+    pub mode: Mode,
+    /// Where the script was read from, to name it in reports.
+    pub source_path: Option<PathBuf>,
+    /// If the script is a lone expression, print its value.
+    pub echo: bool,
+    /// Print each instruction and the stack before it to stderr.
+    pub trace: bool,
+    /// Print a profile report to stderr when the script finishes.
+    pub profile: bool,
+    /// Time each function in the profile.  Implies `profile`.
+    pub profile_time: bool,
+    /// Write the profile as collapsed stacks for flamegraph tools
+    /// here, instead of printing the report.
+    pub collapsed_stacks: Option<PathBuf>,
+    /// Write line coverage here as an lcov tracefile.
+    pub lcov: Option<PathBuf>,
+    /// Write the source here, each line marked with how often it ran.
+    pub annotated: Option<PathBuf>,
+    /// Stop before the first line and take debugger commands on stdin.
+    pub debug: bool,
+}
+
+impl RunOptions {
+    fn profiling(&self) -> bool {
+        self.profile || self.profile_time || self.collapsed_stacks.is_some()
+    }
+
+    fn covering(&self) -> bool {
+        self.lcov.is_some() || self.annotated.is_some()
+    }
+}
+
+pub fn run_from_source(mut reader: impl Read, options: &RunOptions) -> anyhow::Result<()> {
     let mut source = String::new();
     reader.read_to_string(&mut source)?;
+    run_source(&source, options)
+}
 
-    let scanner = Scanner::new(source);
-    for token in scanner.lexemes() {
-        println!("{token:?}");
+pub fn run_source(source: &str, options: &RunOptions) -> anyhow::Result<()> {
+    if options.mode == Mode::Tokens {
+        let scanner = Scanner::new(source.to_string());
+        let mut tokens = scanner.lexemes();
+        let mut out = io::stdout().lock();
+        while let Some(token) = tokens.next() {
+            writeln!(out, "{:>4} {token:?}", tokens.line())?;
+        }
+        return Ok(());
     }
 
+    // only worth trying as an expression if its value will be shown
+    let expression = options.echo.then(|| compile_expression(source).ok());
+    let (chunk, is_expression) = match expression.flatten() {
+        Some(chunk) => (chunk, true),
+        None => (compile(source)?, false),
+    };
+    match options.mode {
+        Mode::Disassemble => {
+            chunk.write_disassembly(&mut io::stdout().lock())?;
+            return Ok(());
+        }
+        Mode::Check => return Ok(()),
+        Mode::Run | Mode::Tokens => {}
+    }
+
+    let mut lox = Lox::new();
+    if options.profiling() {
+        lox.vm().start_profile(options.profile_time);
+    }
+    if options.covering() {
+        lox.vm().start_coverage();
+    }
+    if options.debug {
+        let console = Console::new(io::stdin().lock(), io::stdout(), source);
+        lox.vm().attach_debugger(Debugger::new(Box::new(console)));
+    }
+    lox.vm().set_trace(options.trace);
+    let result = lox.vm().run(&chunk);
+
+    // a profile or coverage of a script that failed still says where
+    // it went
+    if let Some(profile) = lox.vm().take_profile() {
+        match &options.collapsed_stacks {
+            Some(path) => profile.write_collapsed(&mut File::create(path)?)?,
+            None => profile.write_report(&mut io::stderr())?,
+        }
+    }
+    if let Some(coverage) = lox.vm().take_coverage() {
+        if let Some(path) = &options.lcov {
+            let name = match &options.source_path {
+                Some(source_path) => source_path.display().to_string(),
+                None => "-".to_string(),
+            };
+            coverage.write_lcov(&mut File::create(path)?, &name)?;
+        }
+        if let Some(path) = &options.annotated {
+            coverage.write_annotated(&mut File::create(path)?, source)?;
+        }
+    }
+    let value = result?;
+    if is_expression {
+        println!("{value}");
+    }
     Ok(())
 }
 
+/// Read lines from the terminal into a `Repl` until the user quits,
+/// keeping history in `~/.lox_history`.
 pub fn run_repl() -> anyhow::Result<()> {
-    // println!("REPL");
-    run()
-}
-
-pub fn run() -> anyhow::Result<()> {
-    env_logger::init();
-
-    let mut chunk = Chunk::new("test chunk");
-    chunk.emit_constant(1.2, 123);
-    chunk.emit_op(OpCode::Negate, 124);
-    chunk.emit_constant(5.0, 124);
-    chunk.emit_op(OpCode::Subtract, 124);
-    chunk.emit_op(OpCode::Return, 125);
+    let mut editor = DefaultEditor::new()?;
+    let history = env::home_dir().map(|home| home.join(".lox_history"));
+    if let Some(path) = &history {
+        // there's no history the first time
+        let _ = editor.load_history(path);
+    }
 
-    if log_enabled!(Level::Debug) {
-        chunk.disassemble();
+    let mut repl = Repl::new(io::stdout());
+    let mut prompt = Prompt::Ready;
+    loop {
+        let text = match prompt {
+            Prompt::Continue => ". ",
+            _ => "> ",
+        };
+        match editor.readline(text) {
+            Ok(line) => {
+                if !line.trim().is_empty() {
+                    editor.add_history_entry(&line)?;
+                }
+                prompt = repl.feed(&line);
+                if prompt == Prompt::Quit {
+                    break;
+                }
+            }
+            Err(ReadlineError::Interrupted) => {
+                repl.cancel();
+                prompt = Prompt::Ready;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        }
     }
 
-    VM::new(&chunk).run()
+    if let Some(path) = &history {
+        editor.save_history(path)?;
+    }
+    Ok(())
 }
@@ -0,0 +1,49 @@
+//! Hard caps for running untrusted scripts.
+
+use std::fmt;
+
+use crate::stack::FRAMES_MAX;
+
+/// What a VM may use while running one chunk.  `None` means no cap.
+#[derive(Debug, Clone)]
+pub struct Limits {
+    /// Instructions dispatched by a single call to `VM::run`.
+    pub max_instructions: Option<u64>,
+    /// Bytes of heap the script itself allocates.  For now the only
+    /// such allocation is a new global.
+    pub max_heap_bytes: Option<usize>,
+    /// How deeply calls may nest, counting natives that call back
+    /// into the VM.
+    pub max_call_depth: Option<usize>,
+    // TODO: a cap on string length, with its own `Limit`, once the VM
+    // has string values to allocate
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_instructions: None,
+            max_heap_bytes: None,
+            max_call_depth: Some(FRAMES_MAX),
+        }
+    }
+}
+
+/// The limit a script ran into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Instructions,
+    HeapBytes,
+    CallDepth,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let out = match self {
+            Limit::Instructions => "Instruction limit exceeded.",
+            Limit::HeapBytes => "Heap limit exceeded.",
+            Limit::CallDepth => "Call depth limit exceeded.",
+        };
+        write!(f, "{out}")
+    }
+}
@@ -0,0 +1,114 @@
+//! A Lox session for embedding the interpreter in a Rust program.
+//!
+//! ```
+//! use bytecode::lox::Lox;
+//!
+//! let mut lox = Lox::new();
+//! lox.run("var answer = 6 * 7;").unwrap();
+//! assert_eq!(lox.get::<f64>("answer"), Some(42.0));
+//! ```
+
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+use crate::{
+    chunk::Chunk,
+    compiler::{compile, compile_expression},
+    error::{LoxError, Result},
+    limits::Limits,
+    native::NativeFn,
+    opcode::OpCode,
+    value::Value,
+    vm::VM,
+};
+
+/// Compiles and runs source against one VM, so globals defined by one
+/// call to `run` are there for the next.
+pub struct Lox {
+    vm: VM,
+}
+
+impl Lox {
+    pub fn new() -> Self {
+        Lox { vm: VM::new() }
+    }
+
+    /// Compile and run a script.
+    pub fn run(&mut self, source: &str) -> Result<()> {
+        let chunk = compile(source)?;
+        self.vm.run(&chunk)?;
+        Ok(())
+    }
+
+    /// Evaluate a lone expression, with or without a `;` after it, and
+    /// return its value the way `print` would show it.
+    pub fn eval_to_string(&mut self, source: &str) -> Result<String> {
+        let chunk = compile_expression(source)?;
+        Ok(self.vm.run(&chunk)?.to_string())
+    }
+
+    /// Read a global as any Rust type a `Value` converts to, such as
+    /// `f64` or `bool`.  `None` if it isn't defined or has another
+    /// type.
+    pub fn get<T: TryFrom<Value>>(&self, name: &str) -> Option<T> {
+        self.vm.global(name).and_then(|v| T::try_from(v).ok())
+    }
+
+    /// Define or overwrite a global.
+    pub fn set(&mut self, name: &str, value: impl Into<Value>) {
+        self.vm.set_global(name, value.into());
+    }
+
+    /// Call the global function `name` with the given arguments.
+    pub fn call(&mut self, name: &str, args: &[Value]) -> Result<Value> {
+        let callee = self
+            .vm
+            .global(name)
+            .ok_or_else(|| LoxError::runtime(format!("Undefined variable '{name}'.")))?;
+        let argc = u8::try_from(args.len())
+            .map_err(|_| LoxError::runtime("Can't have more than 255 arguments."))?;
+
+        let mut chunk = Chunk::new(name);
+        for &value in std::iter::once(&callee).chain(args) {
+            // at most 256 constants, given the check on argc above
+            let idx = chunk.add_constant(value) as u8;
+            chunk.emit_op(OpCode::Constant, 0);
+            chunk.emit_byte(idx, 0);
+        }
+        chunk.emit_op(OpCode::Call, 0);
+        chunk.emit_byte(argc, 0);
+        chunk.emit_op(OpCode::Return, 0);
+        self.vm.run(&chunk)
+    }
+
+    /// Make a Rust function callable from Lox; see `VM::define_native`.
+    pub fn define_native(&mut self, name: &str, arity: usize, function: NativeFn) {
+        self.vm.define_native(name, arity, function);
+    }
+
+    /// Send the output of `print` to `out` instead of stdout.
+    pub fn set_output(&mut self, out: impl Write + 'static) {
+        self.vm.set_output(Box::new(out));
+    }
+
+    /// Cap what scripts run in this session may use.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.vm.set_limits(limits);
+    }
+
+    /// See `VM::interrupt_flag`.
+    pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+        self.vm.interrupt_flag()
+    }
+
+    pub fn vm(&mut self) -> &mut VM {
+        &mut self.vm
+    }
+}
+
+impl Default for Lox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,21 +1,118 @@
 use std::path::PathBuf;
+use std::process::ExitCode;
 
-use bytecode::{run_from_source, run_repl};
-use clap::Parser;
+use bytecode::error::LoxError;
+use bytecode::{Mode, RunOptions, run_from_source, run_repl, run_source};
+use clap::{ArgGroup, Parser};
 use clio::Input;
 
+/// Run a Lox script, or start a REPL without one.
+///
+/// Exits with 65 for a compile error, 70 for a runtime error and 74
+/// when a file can't be read or written.
+///
+/// Every other option needs a script, from FILE or `--eval`.
 #[derive(Parser, Debug)]
+#[command(group(ArgGroup::new("source").args(["file", "eval"])))]
 struct Args {
     #[arg(value_name = "FILE")]
     file: Option<PathBuf>,
+
+    /// Run CODE instead of a file, printing its value if it is an
+    /// expression
+    #[arg(short, long, value_name = "CODE", conflicts_with = "file", allow_hyphen_values = true)]
+    eval: Option<String>,
+
+    /// Print the tokens instead of running the script
+    #[arg(long, group = "mode", requires = "source")]
+    tokens: bool,
+
+    /// Print the compiled bytecode instead of running the script
+    #[arg(short, long, group = "mode", requires = "source")]
+    disassemble: bool,
+
+    /// Only compile the script, reporting any errors
+    #[arg(long, group = "mode", requires = "source")]
+    check: bool,
+
+    /// Print each instruction and the stack to stderr as it runs
+    #[arg(long, requires = "source")]
+    trace: bool,
+
+    /// Print counts per opcode, line and function when the script ends
+    #[arg(long, requires = "source")]
+    profile: bool,
+
+    /// Also time each function in the profile
+    #[arg(long, requires = "source")]
+    profile_time: bool,
+
+    /// Write the profile to FILE as collapsed stacks for flamegraph tools
+    #[arg(long, value_name = "FILE", requires = "source")]
+    collapsed_stacks: Option<PathBuf>,
+
+    /// Write line coverage to FILE in lcov format
+    #[arg(long, value_name = "FILE", requires = "source")]
+    lcov: Option<PathBuf>,
+
+    /// Write the source to FILE with how often each line ran
+    #[arg(long, value_name = "FILE", requires = "source")]
+    annotated: Option<PathBuf>,
+
+    /// Run under the debugger, taking commands on stdin
+    #[arg(long, requires = "source")]
+    debug: bool,
 }
 
-fn main() -> anyhow::Result<()> {
+fn main() -> ExitCode {
     let args = Args::parse();
+    env_logger::init();
+
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            let status = match e.downcast_ref::<LoxError>() {
+                Some(LoxError::CompileError(_)) => 65,
+                Some(_) => 70,
+                None => 74,
+            };
+            ExitCode::from(status)
+        }
+    }
+}
+
+fn run(args: Args) -> anyhow::Result<()> {
+    let mode = if args.tokens {
+        Mode::Tokens
+    } else if args.disassemble {
+        Mode::Disassemble
+    } else if args.check {
+        Mode::Check
+    } else {
+        Mode::Run
+    };
+    let source_path = match &args.eval {
+        Some(_) => Some(PathBuf::from("-e")),
+        None => args.file.clone(),
+    };
+    let options = RunOptions {
+        mode,
+        source_path,
+        echo: args.eval.is_some(),
+        trace: args.trace,
+        profile: args.profile,
+        profile_time: args.profile_time,
+        collapsed_stacks: args.collapsed_stacks,
+        lcov: args.lcov,
+        annotated: args.annotated,
+        debug: args.debug,
+    };
 
-    if let Some(filename) = &args.file {
-        let reader = Input::new(filename)?;
-        run_from_source(reader)
+    if let Some(code) = &args.eval {
+        run_source(code, &options)
+    } else if let Some(filename) = &args.file {
+        run_from_source(Input::new(filename)?, &options)
     } else {
         run_repl()
     }
@@ -0,0 +1,57 @@
+//! Functions implemented in Rust and callable from Lox.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    error::{LoxError, Result},
+    value::Value,
+    vm::VM,
+};
+
+/// A native gets the VM that called it and its arguments, already
+/// checked against its arity.  Returning an error raises a Lox runtime
+/// error at the call.
+pub type NativeFn = fn(&mut VM, &[Value]) -> Result<Value>;
+
+pub struct Native {
+    pub name: String,
+    pub arity: usize,
+    pub function: NativeFn,
+}
+
+/// The natives every VM starts with.
+pub fn define_defaults(vm: &mut VM) {
+    vm.define_native("clock", 0, clock);
+    vm.define_native("abs", 1, |_, args| math(args, f64::abs));
+    vm.define_native("ceil", 1, |_, args| math(args, f64::ceil));
+    vm.define_native("floor", 1, |_, args| math(args, f64::floor));
+    vm.define_native("round", 1, |_, args| math(args, f64::round));
+    vm.define_native("sqrt", 1, |_, args| math(args, f64::sqrt));
+    vm.define_native("min", 2, |_, args| math2(args, f64::min));
+    vm.define_native("max", 2, |_, args| math2(args, f64::max));
+    vm.define_native("pow", 2, |_, args| math2(args, f64::powf));
+    // TODO: `str(value)`, formatting a value as print does, once the VM
+    // has string values for it to return
+}
+
+/// Seconds since the Unix epoch, for timing scripts.
+fn clock(_vm: &mut VM, _args: &[Value]) -> Result<Value> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| LoxError::runtime("System clock is before 1970."))?;
+    Ok(Value::number(now.as_secs_f64()))
+}
+
+fn math(args: &[Value], op: fn(f64) -> f64) -> Result<Value> {
+    let n = args[0]
+        .as_number()
+        .ok_or_else(|| LoxError::runtime("Argument must be a number."))?;
+    Ok(Value::number(op(n)))
+}
+
+fn math2(args: &[Value], op: fn(f64, f64) -> f64) -> Result<Value> {
+    match (args[0].as_number(), args[1].as_number()) {
+        (Some(a), Some(b)) => Ok(Value::number(op(a, b))),
+        _ => Err(LoxError::runtime("Arguments must be numbers.")),
+    }
+}
@@ -1,4 +1,4 @@
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum OpCode {
     Constant = 0,
@@ -8,8 +8,30 @@ pub enum OpCode {
     Multiply,
     Divide,
     Return,
+    Call,
+    Nil,
+    True,
+    False,
+    Not,
+    Equal,
+    Greater,
+    Less,
+    Pop,
+    Print,
+    DefineGlobal,
+    GetGlobal,
+    SetGlobal,
+
+    // superinstructions: a binary op whose right operand is a constant
+    AddConstant,
+    SubtractConstant,
+    MultiplyConstant,
+    DivideConstant,
 }
 
+// keep in sync with the last variant above
+const OPCODE_COUNT: u8 = OpCode::DivideConstant as u8 + 1;
+
 impl OpCode {
     #[inline]
     pub fn read(byte: u8) -> Self {
@@ -17,4 +39,125 @@ impl OpCode {
         // which takes OpCode and casts to u8
         unsafe { std::mem::transmute(byte) }
     }
+
+    /// Like `read`, but for bytes that might not be an opcode at all.
+    pub fn try_read(byte: u8) -> Option<Self> {
+        (byte < OPCODE_COUNT).then(|| Self::read(byte))
+    }
+
+    /// The name the disassembler and profiler show.
+    pub fn name(self) -> &'static str {
+        match self {
+            OpCode::Constant => "constant",
+            OpCode::Negate => "negate",
+            OpCode::Add => "add",
+            OpCode::Subtract => "subtract",
+            OpCode::Multiply => "multiply",
+            OpCode::Divide => "divide",
+            OpCode::Return => "return",
+            OpCode::Call => "call",
+            OpCode::Nil => "nil",
+            OpCode::True => "true",
+            OpCode::False => "false",
+            OpCode::Not => "not",
+            OpCode::Equal => "equal",
+            OpCode::Greater => "greater",
+            OpCode::Less => "less",
+            OpCode::Pop => "pop",
+            OpCode::Print => "print",
+            OpCode::DefineGlobal => "define_global",
+            OpCode::GetGlobal => "get_global",
+            OpCode::SetGlobal => "set_global",
+            OpCode::AddConstant => "add_constant",
+            OpCode::SubtractConstant => "subtract_constant",
+            OpCode::MultiplyConstant => "multiply_constant",
+            OpCode::DivideConstant => "divide_constant",
+        }
+    }
+
+    /// How many operand bytes follow the opcode in the code stream.
+    pub fn operand_bytes(self) -> usize {
+        match self {
+            OpCode::Constant
+            | OpCode::Call
+            | OpCode::DefineGlobal
+            | OpCode::GetGlobal
+            | OpCode::SetGlobal
+            | OpCode::AddConstant
+            | OpCode::SubtractConstant
+            | OpCode::MultiplyConstant
+            | OpCode::DivideConstant => 1,
+            OpCode::Negate
+            | OpCode::Add
+            | OpCode::Subtract
+            | OpCode::Multiply
+            | OpCode::Divide
+            | OpCode::Return
+            | OpCode::Nil
+            | OpCode::True
+            | OpCode::False
+            | OpCode::Not
+            | OpCode::Equal
+            | OpCode::Greater
+            | OpCode::Less
+            | OpCode::Pop
+            | OpCode::Print => 0,
+        }
+    }
+
+    /// Whether the operand byte is an index into the constant table.
+    pub fn takes_constant(self) -> bool {
+        matches!(
+            self,
+            OpCode::Constant
+                | OpCode::AddConstant
+                | OpCode::SubtractConstant
+                | OpCode::MultiplyConstant
+                | OpCode::DivideConstant
+        )
+    }
+
+    /// Whether the operand byte is an index into the name table.
+    pub fn takes_name(self) -> bool {
+        matches!(self, OpCode::DefineGlobal | OpCode::GetGlobal | OpCode::SetGlobal)
+    }
+
+    /// How many values the instruction pops, and how many it then
+    /// pushes.  `operand` is its operand byte, if it has one.
+    pub fn stack_effect(self, operand: u8) -> (usize, usize) {
+        match self {
+            OpCode::Constant | OpCode::Nil | OpCode::True | OpCode::False | OpCode::GetGlobal => {
+                (0, 1)
+            }
+            OpCode::Call => (operand as usize + 1, 1),
+            OpCode::Pop | OpCode::Print | OpCode::DefineGlobal => (1, 0),
+            OpCode::Not
+            | OpCode::SetGlobal
+            | OpCode::Negate
+            | OpCode::AddConstant
+            | OpCode::SubtractConstant
+            | OpCode::MultiplyConstant
+            | OpCode::DivideConstant => (1, 1),
+            OpCode::Add
+            | OpCode::Subtract
+            | OpCode::Multiply
+            | OpCode::Divide
+            | OpCode::Equal
+            | OpCode::Greater
+            | OpCode::Less => (2, 1),
+            OpCode::Return => (1, 0),
+        }
+    }
+
+    /// The superinstruction that replaces `Constant` followed by
+    /// this op, if there is one.
+    pub fn with_constant(self) -> Option<Self> {
+        match self {
+            OpCode::Add => Some(OpCode::AddConstant),
+            OpCode::Subtract => Some(OpCode::SubtractConstant),
+            OpCode::Multiply => Some(OpCode::MultiplyConstant),
+            OpCode::Divide => Some(OpCode::DivideConstant),
+            _ => None,
+        }
+    }
 }
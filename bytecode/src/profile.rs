@@ -0,0 +1,228 @@
+//! Where a script spends its time: how often each opcode and source
+//! line ran, and what each function cost.  A VM gathers one of these
+//! while profiling is switched on with `VM::start_profile`.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::io;
+use std::io::Write;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::{chunk::Chunk, opcode::OpCode};
+
+/// What one function cost over the whole profile.  Natives count no
+/// instructions of their own.
+#[derive(Debug, Default, Clone)]
+pub struct FunctionStats {
+    pub calls: u64,
+    pub instructions: u64,
+    /// Wall time including callees.  Zero unless timing was on.
+    pub total_time: Duration,
+    /// Wall time less callees.  Zero unless timing was on.
+    pub self_time: Duration,
+}
+
+#[derive(Debug)]
+pub struct Profile {
+    timing: bool,
+    opcodes: [u64; 256],
+    lines: HashMap<u32, u64>,
+    functions: HashMap<String, FunctionStats>,
+    /// Weight of each call stack, keyed by the function names from the
+    /// outside in, separated by `;`.
+    stacks: HashMap<String, u64>,
+    frames: Vec<Frame>,
+}
+
+/// A function that is running right now.
+#[derive(Debug)]
+struct Frame {
+    name: String,
+    /// Executions of each instruction, by offset in the code.
+    counts: Vec<u64>,
+    started: Option<Instant>,
+    in_callees: Duration,
+}
+
+impl Profile {
+    /// With `timing`, also read the clock on every call and return.
+    pub fn new(timing: bool) -> Self {
+        Profile {
+            timing,
+            opcodes: [0; 256],
+            lines: HashMap::new(),
+            functions: HashMap::new(),
+            stacks: HashMap::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    pub(crate) fn enter(&mut self, name: &str, code_len: usize) {
+        self.frames.push(Frame {
+            name: name.to_string(),
+            counts: vec![0; code_len],
+            started: self.timing.then(Instant::now),
+            in_callees: Duration::ZERO,
+        });
+    }
+
+    /// Count one execution of the instruction at `offset` in the
+    /// innermost chunk.
+    #[inline]
+    pub(crate) fn count(&mut self, offset: usize) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.counts[offset] += 1;
+        }
+    }
+
+    /// Leave the chunk entered last, which must be `chunk`.
+    pub(crate) fn exit_chunk(&mut self, chunk: &Chunk) {
+        let Some(frame) = self.frames.pop() else {
+            return;
+        };
+        let instructions = chunk.code().iter().zip(chunk.line_bytes());
+        for ((&op, line), &count) in instructions.zip(&frame.counts) {
+            if count > 0 {
+                self.opcodes[op as usize] += count;
+                *self.lines.entry(line).or_default() += count;
+            }
+        }
+        self.finish(frame);
+    }
+
+    /// Leave the native entered last.
+    pub(crate) fn exit_native(&mut self) {
+        if let Some(frame) = self.frames.pop() {
+            self.finish(frame);
+        }
+    }
+
+    fn finish(&mut self, frame: Frame) {
+        let instructions: u64 = frame.counts.iter().sum();
+        let elapsed = frame.started.map_or(Duration::ZERO, |t| t.elapsed());
+        let self_time = elapsed.saturating_sub(frame.in_callees);
+        if let Some(caller) = self.frames.last_mut() {
+            caller.in_callees += elapsed;
+        }
+
+        let stats = self.functions.entry(frame.name.clone()).or_default();
+        stats.calls += 1;
+        stats.instructions += instructions;
+        stats.total_time += elapsed;
+        stats.self_time += self_time;
+
+        // flamegraphs weigh stacks by time if we have it, and by
+        // instructions otherwise, where a native call counts as one
+        let weight = if self.timing {
+            self_time.as_micros() as u64
+        } else {
+            instructions.max(1)
+        };
+        let mut stack: Vec<&str> = self.frames.iter().map(|f| f.name.as_str()).collect();
+        stack.push(&frame.name);
+        *self.stacks.entry(stack.join(";")).or_default() += weight;
+    }
+
+    /// Instructions executed, over all functions.
+    pub fn instructions(&self) -> u64 {
+        self.opcodes.iter().sum()
+    }
+
+    /// Executions of each opcode that ran at all, most frequent first.
+    pub fn opcodes(&self) -> Vec<(OpCode, u64)> {
+        let mut opcodes: Vec<_> = (0..=u8::MAX)
+            .zip(self.opcodes)
+            .filter(|&(_, count)| count > 0)
+            .map(|(byte, count)| (OpCode::read(byte), count))
+            .collect();
+        opcodes.sort_by_key(|&(op, count)| (Reverse(count), op as u8));
+        opcodes
+    }
+
+    /// Instructions executed on each source line that ran, most first.
+    pub fn lines(&self) -> Vec<(u32, u64)> {
+        let mut lines: Vec<_> = self
+            .lines
+            .iter()
+            .map(|(&line, &count)| (line, count))
+            .collect();
+        lines.sort_by_key(|&(line, count)| (Reverse(count), line));
+        lines
+    }
+
+    /// Every function that was called, costliest first: by self time
+    /// if timing was on, otherwise by instructions.
+    pub fn functions(&self) -> Vec<(&str, &FunctionStats)> {
+        let mut functions: Vec<_> = self
+            .functions
+            .iter()
+            .map(|(name, stats)| (name.as_str(), stats))
+            .collect();
+        functions.sort_by_key(|&(name, stats)| {
+            (Reverse(stats.self_time), Reverse(stats.instructions), name)
+        });
+        functions
+    }
+
+    /// Print the tables of opcodes, lines and functions.
+    pub fn write_report(&self, out: &mut impl Write) -> io::Result<()> {
+        let total = self.instructions();
+        let percent = |count: u64| 100.0 * count as f64 / total.max(1) as f64;
+        writeln!(out, "== profile: {total} instructions ==")?;
+
+        writeln!(out, "\n{:<18} {:>12} {:>7}", "opcode", "count", "%")?;
+        for (op, count) in self.opcodes() {
+            writeln!(
+                out,
+                "{:<18} {count:>12} {:>6.1}%",
+                op.name(),
+                percent(count)
+            )?;
+        }
+
+        writeln!(out, "\n{:<18} {:>12} {:>7}", "line", "count", "%")?;
+        for (line, count) in self.lines() {
+            writeln!(out, "{line:<18} {count:>12} {:>6.1}%", percent(count))?;
+        }
+
+        write!(
+            out,
+            "\n{:<18} {:>12} {:>12}",
+            "function", "calls", "instructions"
+        )?;
+        if self.timing {
+            write!(out, " {:>12} {:>12}", "total ms", "self ms")?;
+        }
+        writeln!(out)?;
+        for (name, stats) in self.functions() {
+            write!(
+                out,
+                "{name:<18} {:>12} {:>12}",
+                stats.calls, stats.instructions
+            )?;
+            if self.timing {
+                let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+                write!(
+                    out,
+                    " {:>12.3} {:>12.3}",
+                    ms(stats.total_time),
+                    ms(stats.self_time)
+                )?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    /// Write the call stacks in the collapsed format flamegraph tools
+    /// read, one `outer;inner weight` line per stack.
+    pub fn write_collapsed(&self, out: &mut impl Write) -> io::Result<()> {
+        let mut stacks: Vec<_> = self.stacks.iter().collect();
+        stacks.sort();
+        for (stack, weight) in stacks {
+            writeln!(out, "{stack} {weight}")?;
+        }
+        Ok(())
+    }
+}
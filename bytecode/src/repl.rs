@@ -0,0 +1,219 @@
+//! The interactive prompt, minus the terminal: a `Repl` takes input a
+//! line at a time against one Lox session, and says whether it needs
+//! more before it can run anything.
+
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::mem;
+use std::rc::Rc;
+
+use crate::{
+    chunk::Chunk,
+    compiler::{compile, compile_expression},
+    error::{LoxError, Result},
+    lox::Lox,
+    scanner::Scanner,
+    token::Token,
+    value::Value,
+};
+
+/// What the REPL wants next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prompt {
+    /// A new entry.
+    Ready,
+    /// The rest of an entry that isn't finished.
+    Continue,
+    /// Nothing; the user asked to quit.
+    Quit,
+}
+
+const HELP: &str = "\
+:dis [CODE]   disassemble CODE, or the last entry run
+:globals      show every global
+:reset        forget every global
+:load FILE    run a script in this session
+:help         show this
+:quit         leave";
+
+pub struct Repl {
+    lox: Lox,
+    out: Shared,
+    /// Lines of an unfinished entry.
+    pending: String,
+    last: Option<Chunk>,
+}
+
+impl Repl {
+    /// A REPL that writes what scripts print, the values of
+    /// expressions and any errors to `out`.
+    pub fn new(out: impl Write + 'static) -> Self {
+        let out = Shared(Rc::new(RefCell::new(Box::new(out))));
+        Repl {
+            lox: session(&out),
+            out,
+            pending: String::new(),
+            last: None,
+        }
+    }
+
+    /// Take one line of input.  Bare expressions have their value
+    /// printed.  A blank line ends an unfinished entry as it is.
+    pub fn feed(&mut self, line: &str) -> Prompt {
+        if self.pending.is_empty() {
+            let line = line.trim();
+            if line.starts_with(':') {
+                return self.command(line);
+            }
+            if line.is_empty() {
+                return Prompt::Ready;
+            }
+        }
+
+        let force = line.trim().is_empty();
+        if !force {
+            self.pending.push_str(line);
+            self.pending.push('\n');
+            if is_incomplete(&self.pending) {
+                return Prompt::Continue;
+            }
+        }
+        let source = mem::take(&mut self.pending);
+        self.eval(&source);
+        Prompt::Ready
+    }
+
+    /// Throw away an unfinished entry.
+    pub fn cancel(&mut self) {
+        self.pending.clear();
+    }
+
+    pub fn lox(&mut self) -> &mut Lox {
+        &mut self.lox
+    }
+
+    fn eval(&mut self, source: &str) {
+        let result = match compile_expression(source) {
+            Ok(chunk) => self.run(chunk).map(|value| {
+                self.print(format_args!("{value}"));
+            }),
+            Err(_) => compile(source).and_then(|chunk| self.run(chunk).map(drop)),
+        };
+        if let Err(e) = result {
+            self.print(format_args!("{e}"));
+        }
+    }
+
+    fn run(&mut self, chunk: Chunk) -> Result<Value> {
+        let chunk = self.last.insert(chunk);
+        self.lox.vm().run(chunk)
+    }
+
+    fn command(&mut self, line: &str) -> Prompt {
+        let (command, arg) = match line.split_once(' ') {
+            Some((command, arg)) => (command, arg.trim()),
+            None => (line, ""),
+        };
+        match (command, arg) {
+            (":dis", "") => match &self.last {
+                Some(chunk) => self.disassemble(chunk),
+                None => self.print(format_args!("Nothing has run yet.")),
+            },
+            (":dis", code) => {
+                match compile_expression(code).or_else(|_| compile(code)) {
+                    Ok(chunk) => self.disassemble(&chunk),
+                    Err(e) => self.print(format_args!("{e}")),
+                }
+            }
+            (":globals", "") => {
+                let mut globals: Vec<_> = self
+                    .lox
+                    .vm()
+                    .globals()
+                    .filter(|(_, value)| value.as_native().is_none())
+                    .map(|(name, value)| format!("{name} = {value}"))
+                    .collect();
+                globals.sort();
+                for global in globals {
+                    self.print(format_args!("{global}"));
+                }
+            }
+            (":reset", "") => {
+                self.lox = session(&self.out);
+                self.last = None;
+            }
+            (":load", path) if !path.is_empty() => match fs::read_to_string(path) {
+                Ok(source) => {
+                    let result = compile(&source).and_then(|chunk| self.run(chunk));
+                    if let Err(e) = result {
+                        self.print(format_args!("{e}"));
+                    }
+                }
+                Err(e) => self.print(format_args!("Can't read {path}: {e}.")),
+            },
+            (":help", "") => self.print(format_args!("{HELP}")),
+            (":quit", "") => return Prompt::Quit,
+            _ => self.print(format_args!("Unknown command '{line}'; try :help.")),
+        }
+        Prompt::Ready
+    }
+
+    fn disassemble(&self, chunk: &Chunk) {
+        let _ = chunk.write_disassembly(&mut self.out.clone());
+    }
+
+    fn print(&self, message: std::fmt::Arguments) {
+        // a REPL that can't write has no one to tell
+        let _ = writeln!(self.out.clone(), "{message}");
+    }
+}
+
+fn session(out: &Shared) -> Lox {
+    let mut lox = Lox::new();
+    lox.set_output(out.clone());
+    lox
+}
+
+/// Whether `source` stops in the middle of something: a string, a
+/// bracket, or a statement that the compiler only finds fault with at
+/// the end.
+fn is_incomplete(source: &str) -> bool {
+    let scanner = Scanner::new(source.to_string());
+    let mut depth = 0;
+    for token in scanner.lexemes() {
+        match token {
+            Token::LeftParen | Token::LeftBrace => depth += 1,
+            Token::RightParen | Token::RightBrace => depth -= 1,
+            Token::Error("Unterminated string.") => return true,
+            _ => {}
+        }
+    }
+    if depth > 0 {
+        return true;
+    }
+    if compile_expression(source).is_ok() {
+        return false;
+    }
+    match compile(source) {
+        Err(LoxError::CompileError(errors)) => {
+            errors.iter().all(|e| e.contains("Error at end:"))
+        }
+        _ => false,
+    }
+}
+
+/// One writer shared by the REPL and the session it runs.
+#[derive(Clone)]
+struct Shared(Rc<RefCell<Box<dyn Write>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.borrow_mut().flush()
+    }
+}
@@ -30,6 +30,11 @@ pub struct Lexemes<'a> {
 }
 
 impl<'a> Lexemes<'a> {
+    /// The 1-based line of the token most recently returned.
+    pub fn line(&self) -> u32 {
+        self.line + 1
+    }
+
     fn consume_if(&mut self, expected: char) -> bool {
         self.chars.next_if(|&(_, c)| c == expected).is_some()
     }
@@ -70,7 +75,7 @@ impl<'a> Lexemes<'a> {
 
         match self.chars.next() {
             Some(_) => Token::String(body),
-            None => panic!("unterminated string on line {}", self.line + 1),
+            None => Token::Error("Unterminated string."),
         }
     }
 
@@ -175,7 +180,7 @@ impl<'a> Iterator for Lexemes<'a> {
                     Token::Greater
                 }
             }
-            _ => todo!(),
+            _ => Token::Error("Unexpected character."),
         };
 
         Some(token)
@@ -0,0 +1,90 @@
+use std::fmt;
+
+use crate::value::Value;
+
+/// How many call frames deep a script may go.
+pub const FRAMES_MAX: usize = 64;
+
+/// One frame can address at most 256 slots with a one-byte operand.
+pub const STACK_MAX: usize = FRAMES_MAX * (u8::MAX as usize + 1);
+
+/// The VM's value stack.  It is allocated once at full size and never
+/// grows, so a pointer to a slot stays valid for as long as the stack
+/// lives.
+///
+/// The unchecked operations are for code that has passed
+/// `Chunk::verify`, which proves the chunk never pops an empty stack,
+/// together with a check that its maximum depth fits in `remaining()`.
+pub struct Stack {
+    slots: Box<[Value]>,
+    top: usize,
+}
+
+impl Stack {
+    pub fn new() -> Self {
+        Stack {
+            slots: vec![Value::nil(); STACK_MAX].into_boxed_slice(),
+            top: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.top
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.top == 0
+    }
+
+    /// Free slots left before the stack overflows.
+    pub fn remaining(&self) -> usize {
+        STACK_MAX - self.top
+    }
+
+    pub fn as_slice(&self) -> &[Value] {
+        &self.slots[..self.top]
+    }
+
+    /// Drop everything above the first `len` values.
+    pub fn truncate(&mut self, len: usize) {
+        self.top = self.top.min(len);
+    }
+
+    /// # Safety
+    ///
+    /// The stack must not be full.
+    #[inline(always)]
+    pub unsafe fn push_unchecked(&mut self, value: Value) {
+        unsafe { *self.slots.get_unchecked_mut(self.top) = value };
+        self.top += 1;
+    }
+
+    /// # Safety
+    ///
+    /// The stack must not be empty.
+    #[inline(always)]
+    pub unsafe fn pop_unchecked(&mut self) -> Value {
+        self.top -= 1;
+        unsafe { *self.slots.get_unchecked(self.top) }
+    }
+
+    /// # Safety
+    ///
+    /// The stack must not be empty.
+    #[inline(always)]
+    pub unsafe fn peek_mut_unchecked(&mut self) -> &mut Value {
+        unsafe { self.slots.get_unchecked_mut(self.top - 1) }
+    }
+}
+
+impl Default for Stack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Stack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_slice())
+    }
+}
@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Token<'a> {
     // one character
     LeftParen,
@@ -47,5 +47,52 @@ pub enum Token<'a> {
     While,
 
     // special
+    Error(&'static str),
     EOF,
 }
+
+impl Token<'_> {
+    /// The source text of the token, as near as we can tell: strings
+    /// lose their quotes, and errors and the end of input have none.
+    pub fn lexeme(&self) -> &str {
+        match self {
+            Token::LeftParen => "(",
+            Token::RightParen => ")",
+            Token::LeftBrace => "{",
+            Token::RightBrace => "}",
+            Token::Comma => ",",
+            Token::Dot => ".",
+            Token::Minus => "-",
+            Token::Plus => "+",
+            Token::Semicolon => ";",
+            Token::Slash => "/",
+            Token::Star => "*",
+            Token::Bang => "!",
+            Token::BangEqual => "!=",
+            Token::Equal => "=",
+            Token::EqualEqual => "==",
+            Token::Greater => ">",
+            Token::GreaterEqual => ">=",
+            Token::Less => "<",
+            Token::LessEqual => "<=",
+            Token::Identifier(s) | Token::String(s) | Token::Number(s) => s,
+            Token::And => "and",
+            Token::Class => "class",
+            Token::Else => "else",
+            Token::False => "false",
+            Token::For => "for",
+            Token::Fun => "fun",
+            Token::If => "if",
+            Token::Nil => "nil",
+            Token::Or => "or",
+            Token::Print => "print",
+            Token::Return => "return",
+            Token::Super => "super",
+            Token::This => "this",
+            Token::True => "true",
+            Token::Var => "var",
+            Token::While => "while",
+            Token::Error(_) | Token::EOF => "",
+        }
+    }
+}
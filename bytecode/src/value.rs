@@ -1 +1,216 @@
-pub type Value = f64;
+//! Runtime values.  The representation is chosen at compile time: a
+//! plain enum by default, or a NaN-boxed `u64` with the `nan-boxing`
+//! feature.  Both expose the same API, so nothing outside this module
+//! should care which one is in use.
+
+use std::fmt;
+
+use lox_format::Number;
+
+#[cfg(not(feature = "nan-boxing"))]
+#[derive(Clone, Copy, PartialEq)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    Native(usize),
+}
+
+#[cfg(not(feature = "nan-boxing"))]
+impl Value {
+    #[inline]
+    pub fn nil() -> Self {
+        Value::Nil
+    }
+
+    #[inline]
+    pub fn bool(b: bool) -> Self {
+        Value::Bool(b)
+    }
+
+    #[inline]
+    pub fn number(n: f64) -> Self {
+        Value::Number(n)
+    }
+
+    /// A native function, by its index in the VM's native table.
+    #[inline]
+    pub fn native(idx: usize) -> Self {
+        Value::Native(idx)
+    }
+
+    #[inline]
+    pub fn is_nil(self) -> bool {
+        matches!(self, Value::Nil)
+    }
+
+    #[inline]
+    pub fn as_bool(self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn as_number(self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn as_native(self) -> Option<usize> {
+        match self {
+            Value::Native(idx) => Some(idx),
+            _ => None,
+        }
+    }
+}
+
+/// A value packed into the bits of a quiet NaN.  Any `u64` that isn't
+/// a quiet NaN with all of `QNAN` set is a number; otherwise the low
+/// three bits are a tag, and a native function keeps its index above
+/// them.  The sign bit is kept free for object pointers.
+#[cfg(feature = "nan-boxing")]
+#[derive(Clone, Copy)]
+pub struct Value(u64);
+
+#[cfg(feature = "nan-boxing")]
+impl Value {
+    const QNAN: u64 = 0x7ffc_0000_0000_0000;
+    const TAG_NIL: u64 = 1;
+    const TAG_FALSE: u64 = 2;
+    const TAG_TRUE: u64 = 3;
+    const TAG_NATIVE: u64 = 4;
+    const TAG_MASK: u64 = 0b111;
+    const SIGN_BIT: u64 = 0x8000_0000_0000_0000;
+
+    const NIL: Value = Value(Self::QNAN | Self::TAG_NIL);
+    const FALSE: Value = Value(Self::QNAN | Self::TAG_FALSE);
+    const TRUE: Value = Value(Self::QNAN | Self::TAG_TRUE);
+
+    #[inline]
+    pub fn nil() -> Self {
+        Self::NIL
+    }
+
+    #[inline]
+    pub fn bool(b: bool) -> Self {
+        if b { Self::TRUE } else { Self::FALSE }
+    }
+
+    #[inline]
+    pub fn number(n: f64) -> Self {
+        Value(n.to_bits())
+    }
+
+    /// A native function, by its index in the VM's native table.
+    #[inline]
+    pub fn native(idx: usize) -> Self {
+        Value(Self::QNAN | (idx as u64) << 3 | Self::TAG_NATIVE)
+    }
+
+    #[inline]
+    pub fn is_nil(self) -> bool {
+        self.0 == Self::NIL.0
+    }
+
+    #[inline]
+    pub fn as_bool(self) -> Option<bool> {
+        match self.0 {
+            b if b == Self::TRUE.0 => Some(true),
+            b if b == Self::FALSE.0 => Some(false),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn as_number(self) -> Option<f64> {
+        if self.0 & Self::QNAN != Self::QNAN {
+            Some(f64::from_bits(self.0))
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub fn as_native(self) -> Option<usize> {
+        let tagged = Self::SIGN_BIT | Self::QNAN | Self::TAG_MASK;
+        if self.0 & tagged == Self::QNAN | Self::TAG_NATIVE {
+            Some(((self.0 & !Self::QNAN) >> 3) as usize)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(feature = "nan-boxing")]
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.as_number(), other.as_number()) {
+            (Some(a), Some(b)) => a == b,
+            _ => self.0 == other.0,
+        }
+    }
+}
+
+impl Value {
+    /// Lox's notion of false: `nil` and `false`, and nothing else.
+    #[inline]
+    pub fn is_falsey(self) -> bool {
+        self.is_nil() || self.as_bool() == Some(false)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::number(n)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::bool(b)
+    }
+}
+
+impl TryFrom<Value> for f64 {
+    type Error = Value;
+
+    fn try_from(value: Value) -> Result<Self, Value> {
+        value.as_number().ok_or(value)
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = Value;
+
+    fn try_from(value: Value) -> Result<Self, Value> {
+        value.as_bool().ok_or(value)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(n) = self.as_number() {
+            write!(f, "{}", Number(n))
+        } else if let Some(b) = self.as_bool() {
+            write!(f, "{b}")
+        } else if self.as_native().is_some() {
+            write!(f, "<native fn>")
+        } else {
+            write!(f, "nil")
+        }
+    }
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.as_number() {
+            Some(n) => write!(f, "{n:?}"),
+            None => write!(f, "{self}"),
+        }
+    }
+}
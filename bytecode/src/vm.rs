@@ -1,68 +1,570 @@
-use crate::{chunk::Chunk, error::LoxError, opcode::OpCode, value::Value};
-use log::{Level, log_enabled};
+use std::collections::HashMap;
+use std::io;
+use std::io::Write;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-pub struct VM<'a> {
-    chunk: &'a Chunk,
+use crate::{
+    chunk::Chunk,
+    coverage::Coverage,
+    debugger::Debugger,
+    error::{LoxError, Result},
+    limits::{Limit, Limits},
+    native::{self, Native, NativeFn},
+    opcode::OpCode,
+    profile::Profile,
+    stack::Stack,
+    value::Value,
+};
+
+pub struct VM {
     ip: usize,
-    stack: Vec<Value>,
+    stack: Stack,
+    natives: Vec<Native>,
+    globals: HashMap<String, Value>,
+    out: Box<dyn Write>,
+    limits: Limits,
+    interrupt: Arc<AtomicBool>,
+    heap_bytes: usize,
+    call_depth: usize,
+    profile: Option<Profile>,
+    coverage: Option<Coverage>,
+    debugger: Option<Debugger>,
+    trace: bool,
+    dispatched: u64,
 }
 
-impl<'a> VM<'a> {
-    pub fn new(chunk: &'a Chunk) -> Self {
-        Self {
-            chunk,
-            ip: 0,
-            stack: Vec::with_capacity(256),
+/// How many instructions run between checks of the interrupt flag.
+const CHECK_INTERVAL: u64 = 1024;
+
+/// A read position in code that has passed `Chunk::verify`, so every
+/// instruction and operand it reads is known to be in bounds.
+struct Cursor<'a> {
+    start: *const u8,
+    ip: *const u8,
+    _code: PhantomData<&'a [u8]>,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(code: &'a [u8]) -> Self {
+        Cursor {
+            start: code.as_ptr(),
+            ip: code.as_ptr(),
+            _code: PhantomData,
         }
     }
 
+    #[inline(always)]
     fn read_byte(&mut self) -> u8 {
-        let b = self.chunk.code()[self.ip];
-        self.ip += 1;
-        b
+        // SAFETY: verified code always ends in Return, and every
+        // operand read belongs to an instruction that fits in the
+        // code, so ip never runs past the end before we stop
+        unsafe {
+            let b = *self.ip;
+            self.ip = self.ip.add(1);
+            b
+        }
+    }
+
+    fn offset(&self) -> usize {
+        // SAFETY: both pointers come from the same slice
+        unsafe { self.ip.offset_from(self.start) as usize }
+    }
+}
+
+impl VM {
+    pub fn new() -> Self {
+        let mut vm = Self {
+            ip: 0,
+            stack: Stack::new(),
+            natives: Vec::new(),
+            globals: HashMap::new(),
+            out: Box::new(io::stdout()),
+            limits: Limits::default(),
+            interrupt: Arc::new(AtomicBool::new(false)),
+            heap_bytes: 0,
+            call_depth: 0,
+            profile: None,
+            coverage: None,
+            debugger: None,
+            trace: false,
+            dispatched: 0,
+        };
+        native::define_defaults(&mut vm);
+        vm
+    }
+
+    /// How many instructions `run` has dispatched so far.
+    pub fn dispatch_count(&self) -> u64 {
+        self.dispatched
+    }
+
+    /// Make a Rust function callable from Lox as the global `name`,
+    /// replacing any previous global of that name.
+    pub fn define_native(&mut self, name: &str, arity: usize, function: NativeFn) {
+        self.natives.push(Native {
+            name: name.to_string(),
+            arity,
+            function,
+        });
+        let value = Value::native(self.natives.len() - 1);
+        self.globals.insert(name.to_string(), value);
+    }
+
+    pub fn global(&self, name: &str) -> Option<Value> {
+        self.globals.get(name).copied()
+    }
+
+    /// Every global, natives included, in no particular order.
+    pub fn globals(&self) -> impl Iterator<Item = (&str, Value)> {
+        self.globals.iter().map(|(name, &value)| (name.as_str(), value))
+    }
+
+    /// The value stack, bottom first.
+    pub fn stack(&self) -> &[Value] {
+        self.stack.as_slice()
+    }
+
+    /// Define or overwrite a global.
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.globals.insert(name.to_string(), value);
+    }
+
+    /// Send the output of `print` somewhere other than stdout.
+    pub fn set_output(&mut self, out: Box<dyn Write>) {
+        self.out = out;
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// A flag another thread can set to stop the running script.  The
+    /// VM notices within a few instructions, fails the run with
+    /// `LoxError::Interrupted` and clears the flag again.
+    pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.interrupt)
+    }
+
+    /// Profile everything run from now on, throwing away any profile
+    /// already gathered.  With `timing`, functions are timed too.
+    pub fn start_profile(&mut self, timing: bool) {
+        self.profile = Some(Profile::new(timing));
+    }
+
+    /// Stop profiling and hand back what was gathered.
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        self.profile.take()
+    }
+
+    /// Record which lines run from now on, throwing away any coverage
+    /// already gathered.
+    pub fn start_coverage(&mut self) {
+        self.coverage = Some(Coverage::new());
+    }
+
+    /// Stop recording coverage and hand back what was gathered.
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+
+    /// Let `debugger` pause everything run from now on.
+    pub fn attach_debugger(&mut self, debugger: Debugger) {
+        self.debugger = Some(debugger);
+    }
+
+    pub fn detach_debugger(&mut self) -> Option<Debugger> {
+        self.debugger.take()
+    }
+
+    /// Print each instruction, with the stack before it, to stderr as
+    /// it runs.
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    /// Run the chunk to its `Return`, and hand back the value it
+    /// returned.
+    pub fn run(&mut self, chunk: &Chunk) -> Result<Value> {
+        let max_depth = chunk.verify()?;
+        // a native may be running us from inside another chunk, whose
+        // values stay below ours
+        let base = self.stack.len();
+        if max_depth > self.stack.remaining() {
+            // TODO: once there are calls this belongs in the call
+            // instruction, checked against each callee's depth
+            self.ip = 0;
+            let error = LoxError::runtime("Stack overflow.");
+            return Err(self.runtime_error(chunk, base, error));
+        }
+
+        let mut cursor = Cursor::new(chunk.code());
+        let instrument = self.profile.is_some()
+            || self.coverage.is_some()
+            || self.debugger.is_some()
+            || self.trace;
+        let result = if instrument {
+            self.enter_chunk(chunk);
+            let result = self.dispatch::<true>(chunk, &mut cursor);
+            self.exit_chunk(chunk);
+            result
+        } else {
+            self.dispatch::<false>(chunk, &mut cursor)
+        };
+        self.ip = cursor.offset();
+        result.map_err(|e| self.runtime_error(chunk, base, e))
+    }
+
+    /// Attach the line we stopped on, and throw away whatever the
+    /// failed chunk left on the stack.
+    fn runtime_error(&mut self, chunk: &Chunk, base: usize, error: LoxError) -> LoxError {
+        self.stack.truncate(base);
+        error.at_line(chunk.line_at(self.ip.saturating_sub(1)))
+    }
+
+    /// Tell the profiler, coverage and debugger, whichever are on, that
+    /// `chunk` starts running.
+    fn enter_chunk(&mut self, chunk: &Chunk) {
+        if let Some(profile) = &mut self.profile {
+            profile.enter(chunk.function_name(), chunk.code().len());
+        }
+        if let Some(coverage) = &mut self.coverage {
+            coverage.enter(chunk);
+        }
+        if let Some(debugger) = &mut self.debugger {
+            debugger.enter_chunk(chunk);
+        }
+    }
+
+    fn exit_chunk(&mut self, chunk: &Chunk) {
+        if let Some(profile) = &mut self.profile {
+            profile.exit_chunk(chunk);
+        }
+        if let Some(coverage) = &mut self.coverage {
+            coverage.exit(chunk);
+        }
+        if let Some(debugger) = &mut self.debugger {
+            debugger.exit();
+        }
+    }
+
+    fn enter_native(&mut self, name: &str) -> Result<()> {
+        if let Some(profile) = &mut self.profile {
+            profile.enter(name, 0);
+        }
+        self.with_debugger(|debugger, vm| debugger.enter_native(vm, name))
+    }
+
+    fn exit_native(&mut self) {
+        if let Some(profile) = &mut self.profile {
+            profile.exit_native();
+        }
+        if let Some(debugger) = &mut self.debugger {
+            debugger.exit();
+        }
+    }
+
+    /// Count one execution of the instruction at `offset`, trace it,
+    /// and give the debugger its chance to pause before it.
+    #[inline]
+    fn instrument(&mut self, chunk: &Chunk, offset: usize) -> Result<()> {
+        if self.trace {
+            let mut err = io::stderr().lock();
+            // tracing is best effort
+            let _ = writeln!(err, "          {:?}", self.stack);
+            let _ = chunk.write_instruction(&mut err, offset);
+        }
+        if let Some(profile) = &mut self.profile {
+            profile.count(offset);
+        }
+        if let Some(coverage) = &mut self.coverage {
+            coverage.count(offset);
+        }
+        self.with_debugger(|debugger, vm| debugger.at(vm, offset))
+    }
+
+    /// Run `f` on the debugger, if there is one, while it looks at us.
+    fn with_debugger(&mut self, f: impl FnOnce(&mut Debugger, &VM) -> Result<()>) -> Result<()> {
+        let Some(mut debugger) = self.debugger.take() else {
+            return Ok(());
+        };
+        let result = f(&mut debugger, self);
+        self.debugger = Some(debugger);
+        result
     }
 
-    fn read_opcode(&mut self) -> OpCode {
-        OpCode::read(self.read_byte())
+    /// Called every `CHECK_INTERVAL` instructions with how many this
+    /// run has executed; returns when to check next.
+    fn check_limits(&self, executed: u64) -> Result<u64> {
+        if self.interrupt.load(Ordering::Relaxed) {
+            self.interrupt.store(false, Ordering::Relaxed);
+            return Err(LoxError::Interrupted { line: 0 });
+        }
+        match self.limits.max_instructions {
+            Some(max) if executed >= max => Err(limit_exceeded(Limit::Instructions)),
+            Some(max) => Ok(max.min(executed + CHECK_INTERVAL)),
+            None => Ok(executed + CHECK_INTERVAL),
+        }
     }
 
-    fn read_constant(&mut self) -> Value {
-        let idx = self.read_byte() as usize;
-        self.chunk.constant(idx)
+    /// Account for heap the script allocates.
+    fn charge_heap(&mut self, bytes: usize) -> Result<()> {
+        self.heap_bytes += bytes;
+        match self.limits.max_heap_bytes {
+            Some(max) if self.heap_bytes > max => Err(limit_exceeded(Limit::HeapBytes)),
+            _ => Ok(()),
+        }
     }
 
-    pub fn run(&mut self) -> anyhow::Result<()> {
-        loop {
-            if log_enabled!(Level::Debug) {
-                println!("stack: {:?}  ip: {}", &self.stack, self.ip);
+    /// Runs verified code until it returns.  Errors don't have a line
+    /// yet; `run` adds it once it knows where we stopped.
+    ///
+    /// Profiling, coverage, tracing and debugging get a separate
+    /// instantiation so the usual loop doesn't pay for them.
+    fn dispatch<const INSTRUMENT: bool>(
+        &mut self,
+        chunk: &Chunk,
+        cursor: &mut Cursor,
+    ) -> Result<Value> {
+        let constants = chunk.constants();
+        // counted in a local so it stays in a register
+        let mut dispatched = 0;
+        let mut next_check = 0;
+
+        // SAFETY: for all the unchecked stack operations below, run has
+        // verified that the chunk never pops more than it pushed, and
+        // that its deepest point fits in the stack.
+        let result = loop {
+            if dispatched == next_check {
+                match self.check_limits(dispatched) {
+                    Ok(next) => next_check = next,
+                    Err(e) => break Err(e),
+                }
+            }
+
+            let op = OpCode::read(cursor.read_byte());
+            // an error here is reported against the instruction we
+            // read, so that is the one we stop before
+            if INSTRUMENT && let Err(e) = self.instrument(chunk, cursor.offset() - 1) {
+                break Err(e);
             }
 
-            match self.read_opcode() {
+            dispatched += 1;
+            let stack = &mut self.stack;
+            let step = match op {
                 OpCode::Constant => {
-                    let val = self.read_constant();
-                    self.stack.push(val);
+                    let idx = cursor.read_byte() as usize;
+                    // SAFETY: verify checked every constant operand
+                    unsafe { stack.push_unchecked(*constants.get_unchecked(idx)) };
+                    Ok(())
                 }
                 OpCode::Negate => {
-                    let val = self.stack.pop().ok_or(LoxError::RuntimeError)?;
-                    self.stack.push(-val);
+                    let top = unsafe { stack.peek_mut_unchecked() };
+                    match top.as_number() {
+                        Some(n) => {
+                            *top = Value::number(-n);
+                            Ok(())
+                        }
+                        None => Err(LoxError::runtime("Operand must be a number.")),
+                    }
+                }
+                OpCode::Add      => binary_op(stack, |a, b| a + b),
+                OpCode::Subtract => binary_op(stack, |a, b| a - b),
+                OpCode::Multiply => binary_op(stack, |a, b| a * b),
+                OpCode::Divide   => binary_op(stack, |a, b| a / b),
+                OpCode::AddConstant      => constant_op(stack, cursor, constants, |a, b| a + b),
+                OpCode::SubtractConstant => constant_op(stack, cursor, constants, |a, b| a - b),
+                OpCode::MultiplyConstant => constant_op(stack, cursor, constants, |a, b| a * b),
+                OpCode::DivideConstant   => constant_op(stack, cursor, constants, |a, b| a / b),
+                OpCode::Call => {
+                    let argc = cursor.read_byte() as usize;
+                    self.call(argc)
+                }
+                OpCode::Nil => {
+                    unsafe { stack.push_unchecked(Value::nil()) };
+                    Ok(())
+                }
+                OpCode::True => {
+                    unsafe { stack.push_unchecked(Value::bool(true)) };
+                    Ok(())
+                }
+                OpCode::False => {
+                    unsafe { stack.push_unchecked(Value::bool(false)) };
+                    Ok(())
+                }
+                OpCode::Not => {
+                    let top = unsafe { stack.peek_mut_unchecked() };
+                    *top = Value::bool(top.is_falsey());
+                    Ok(())
+                }
+                OpCode::Equal => {
+                    let rhs = unsafe { stack.pop_unchecked() };
+                    let top = unsafe { stack.peek_mut_unchecked() };
+                    *top = Value::bool(*top == rhs);
+                    Ok(())
+                }
+                OpCode::Greater => comparison_op(stack, |a, b| a > b),
+                OpCode::Less    => comparison_op(stack, |a, b| a < b),
+                OpCode::Pop => {
+                    unsafe { stack.pop_unchecked() };
+                    Ok(())
+                }
+                OpCode::Print => {
+                    let val = unsafe { stack.pop_unchecked() };
+                    writeln!(self.out, "{val}")
+                        .map_err(|e| LoxError::runtime(format!("Could not print: {e}.")))
+                }
+                OpCode::DefineGlobal => {
+                    let name = chunk.name(cursor.read_byte() as usize);
+                    let val = unsafe { stack.pop_unchecked() };
+                    self.define_global(name, val)
+                }
+                OpCode::GetGlobal => {
+                    let name = chunk.name(cursor.read_byte() as usize);
+                    match self.globals.get(name) {
+                        Some(&val) => {
+                            unsafe { stack.push_unchecked(val) };
+                            Ok(())
+                        }
+                        None => Err(undefined_variable(name)),
+                    }
+                }
+                OpCode::SetGlobal => {
+                    let name = chunk.name(cursor.read_byte() as usize);
+                    let val = unsafe { *stack.peek_mut_unchecked() };
+                    match self.globals.get_mut(name) {
+                        Some(slot) => {
+                            *slot = val;
+                            Ok(())
+                        }
+                        None => Err(undefined_variable(name)),
+                    }
                 }
-                OpCode::Add      => self.binary_op(|a, b| a + b)?,
-                OpCode::Subtract => self.binary_op(|a, b| a - b)?,
-                OpCode::Multiply => self.binary_op(|a, b| a * b)?,
-                OpCode::Divide   => self.binary_op(|a, b| a / b)?,
                 OpCode::Return => {
-                    let val = self.stack.pop().ok_or(LoxError::RuntimeError)?;
-                    println!("return {val:?}");
-                    return Ok(());
+                    break Ok(unsafe { stack.pop_unchecked() });
                 }
+            };
+            if let Err(e) = step {
+                break Err(e);
             }
+        };
+
+        self.dispatched += dispatched;
+        result
+    }
+
+    fn define_global(&mut self, name: &str, val: Value) -> Result<()> {
+        if !self.globals.contains_key(name) {
+            self.charge_heap(name.len() + size_of::<Value>())?;
         }
+        self.globals.insert(name.to_string(), val);
+        Ok(())
     }
 
-    fn binary_op(&mut self, op: impl Fn(Value, Value) -> Value) -> anyhow::Result<()> {
-        let rhs = self.stack.pop().ok_or(LoxError::RuntimeError)?;
-        let lhs = self.stack.pop().ok_or(LoxError::RuntimeError)?;
-        self.stack.push(op(lhs, rhs));
+    /// Call the value sitting below the top `argc` arguments, leaving
+    /// its result in place of it and the arguments.
+    fn call(&mut self, argc: usize) -> Result<()> {
+        // verified code has the callee and all its arguments on the stack
+        let callee_slot = self.stack.len() - argc - 1;
+        let callee = self.stack.as_slice()[callee_slot];
+        let Some(native) = callee.as_native().map(|idx| &self.natives[idx]) else {
+            return Err(LoxError::runtime("Can only call functions and classes."));
+        };
+        if argc != native.arity {
+            return Err(LoxError::runtime(format!(
+                "Expected {} arguments but got {argc}.",
+                native.arity
+            )));
+        }
+
+        if self.limits.max_call_depth.is_some_and(|max| self.call_depth >= max) {
+            return Err(limit_exceeded(Limit::CallDepth));
+        }
+
+        let function = native.function;
+        if self.profile.is_some() || self.debugger.is_some() {
+            let name = native.name.clone();
+            if let Err(e) = self.enter_native(&name) {
+                self.exit_native();
+                return Err(e);
+            }
+        }
+
+        let args = self.stack.as_slice()[callee_slot + 1..].to_vec();
+        self.call_depth += 1;
+        let result = function(self, &args);
+        self.call_depth -= 1;
+        self.exit_native();
+        let result = result?;
+        self.stack.truncate(callee_slot);
+        // SAFETY: we just freed at least the callee's slot
+        unsafe { self.stack.push_unchecked(result) };
         Ok(())
     }
 }
+
+impl Default for VM {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Pops the right operand and overwrites the left one, which is left
+/// on top of the stack, with the result.
+#[inline(always)]
+fn binary_op(stack: &mut Stack, op: impl Fn(f64, f64) -> f64) -> Result<()> {
+    // SAFETY: verified code has both operands on the stack
+    let rhs = unsafe { stack.pop_unchecked() };
+    let top = unsafe { stack.peek_mut_unchecked() };
+    match (top.as_number(), rhs.as_number()) {
+        (Some(lhs), Some(rhs)) => {
+            *top = Value::number(op(lhs, rhs));
+            Ok(())
+        }
+        _ => Err(LoxError::runtime("Operands must be numbers.")),
+    }
+}
+
+#[inline(always)]
+fn comparison_op(stack: &mut Stack, op: impl Fn(f64, f64) -> bool) -> Result<()> {
+    // SAFETY: verified code has both operands on the stack
+    let rhs = unsafe { stack.pop_unchecked() };
+    let top = unsafe { stack.peek_mut_unchecked() };
+    match (top.as_number(), rhs.as_number()) {
+        (Some(lhs), Some(rhs)) => {
+            *top = Value::bool(op(lhs, rhs));
+            Ok(())
+        }
+        _ => Err(LoxError::runtime("Operands must be numbers.")),
+    }
+}
+
+fn limit_exceeded(limit: Limit) -> LoxError {
+    LoxError::LimitExceeded { limit, line: 0 }
+}
+
+fn undefined_variable(name: &str) -> LoxError {
+    LoxError::runtime(format!("Undefined variable '{name}'."))
+}
+
+#[inline(always)]
+fn constant_op(
+    stack: &mut Stack,
+    cursor: &mut Cursor,
+    constants: &[Value],
+    op: impl Fn(f64, f64) -> f64,
+) -> Result<()> {
+    let idx = cursor.read_byte() as usize;
+    // SAFETY: verify checked every constant operand, and verified code
+    // has the left operand on the stack
+    let rhs = unsafe { constants.get_unchecked(idx) };
+    let top = unsafe { stack.peek_mut_unchecked() };
+    match (top.as_number(), rhs.as_number()) {
+        (Some(lhs), Some(rhs)) => {
+            *top = Value::number(op(lhs, rhs));
+            Ok(())
+        }
+        _ => Err(LoxError::runtime("Operands must be numbers.")),
+    }
+}
@@ -0,0 +1,118 @@
+use std::fs;
+use std::process::Command;
+use std::process::Output;
+
+fn bytecode(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_bytecode"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+#[test]
+fn evaluates_expressions_and_statements() {
+    let output = bytecode(&["-e", "1 + 2 * 3"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "7\n");
+
+    let output = bytecode(&["-e", "var a = 2; print a;"]);
+    assert_eq!(stdout(&output), "2\n");
+}
+
+#[test]
+fn exit_status_says_what_went_wrong() {
+    let output = bytecode(&["-e", "1 +"]);
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(stderr(&output), "[line 1] Error at end: Expect expression.\n");
+
+    let output = bytecode(&["-e", "-nil"]);
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(stderr(&output), "Operand must be a number.\n[line 1] in script\n");
+
+    let output = bytecode(&["/no/such/file.lox"]);
+    assert_eq!(output.status.code(), Some(74));
+}
+
+#[test]
+fn dumps_tokens() {
+    let output = bytecode(&["--tokens", "-e", "var a =\n1;"]);
+    assert_eq!(
+        stdout(&output),
+        "   1 Var\n   1 Identifier(\"a\")\n   1 Equal\n   2 Number(\"1\")\n   2 Semicolon\n   2 EOF\n"
+    );
+}
+
+#[test]
+fn dumps_disassembly_without_running() {
+    let output = bytecode(&["--disassemble", "-e", "print 1 + 2;"]);
+    assert_eq!(
+        stdout(&output),
+        "== script ==\n\
+         0000    1 constant            0 '1'\n\
+         0002    | add_constant        1 '2'\n\
+         0004    | print\n\
+         0005    | nil\n\
+         0006    | return\n"
+    );
+}
+
+#[test]
+fn checks_without_running() {
+    let path = std::env::temp_dir().join("lox-cli-check.lox");
+    fs::write(&path, "print 1;\nprint -nil;\n").unwrap();
+    let output = bytecode(&["--check", path.to_str().unwrap()]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "");
+
+    fs::write(&path, "print 1;\nprint );\nvar;\n").unwrap();
+    let output = bytecode(&["--check", path.to_str().unwrap()]);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        stderr(&output),
+        "[line 2] Error at ')': Expect expression.\n\
+         [line 3] Error at ';': Expect variable name.\n"
+    );
+}
+
+#[test]
+fn traces_to_stderr() {
+    let output = bytecode(&["--trace", "-e", "print 1;"]);
+    assert_eq!(stdout(&output), "1\n");
+    assert_eq!(
+        stderr(&output),
+        "          []\n\
+         0000    1 constant            0 '1'\n\
+         \x20         [1.0]\n\
+         0002    1 print\n\
+         \x20         []\n\
+         0003    1 nil\n\
+         \x20         [nil]\n\
+         0004    1 return\n"
+    );
+}
+
+#[test]
+fn options_need_a_script() {
+    // without the check these would start the REPL, which ignores them
+    for flag in ["--check", "--tokens", "--disassemble", "--trace", "--profile", "--debug"] {
+        let output = bytecode(&[flag]);
+        assert_eq!(output.status.code(), Some(2), "{flag}");
+        assert_eq!(stdout(&output), "", "{flag}");
+        assert!(
+            stderr(&output).contains("required arguments were not provided"),
+            "{flag}: {}",
+            stderr(&output)
+        );
+    }
+    let output = bytecode(&["--lcov", "out.info"]);
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,71 @@
+use bytecode::coverage::Coverage;
+use bytecode::lox::Lox;
+
+fn covered(source: &str) -> Coverage {
+    let mut lox = Lox::new();
+    lox.set_output(std::io::sink());
+    lox.vm().start_coverage();
+    let _ = lox.run(source);
+    lox.vm().take_coverage().unwrap()
+}
+
+#[test]
+fn records_lines_that_ran() {
+    let coverage = covered("var a = 1;\n\n// nothing here\nprint a;\n");
+    assert_eq!(coverage.hits(1), Some(1));
+    assert_eq!(coverage.hits(2), None);
+    assert_eq!(coverage.hits(3), None);
+    assert_eq!(coverage.hits(4), Some(1));
+    assert_eq!(coverage.hits(5), None);
+}
+
+#[test]
+fn counts_each_run_of_a_line() {
+    let mut lox = Lox::new();
+    lox.set_output(std::io::sink());
+    lox.vm().start_coverage();
+    for _ in 0..3 {
+        lox.run("print 1 + 2 * 3;").unwrap();
+    }
+    let coverage = lox.vm().take_coverage().unwrap();
+    assert_eq!(coverage.hits(1), Some(3));
+}
+
+#[test]
+fn lines_after_an_error_are_missed() {
+    let coverage = covered("var a = 1;\n-nil;\nprint a;");
+    assert_eq!(coverage.hits(2), Some(1));
+    assert_eq!(coverage.hits(3), Some(0));
+    assert_eq!(coverage.lines_found(), 3);
+    assert_eq!(coverage.lines_hit(), 2);
+}
+
+#[test]
+fn writes_lcov() {
+    let coverage = covered("var a = 1;\n-nil;\n\nprint a;");
+    let mut out = Vec::new();
+    coverage.write_lcov(&mut out, "test.lox").unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "TN:\n\
+         SF:test.lox\n\
+         DA:1,1\n\
+         DA:2,1\n\
+         DA:4,0\n\
+         LF:3\n\
+         LH:2\n\
+         end_of_record\n"
+    );
+}
+
+#[test]
+fn writes_annotated_source() {
+    let source = "var a = 1;\n-nil;\n\nprint a;";
+    let coverage = covered(source);
+    let mut out = Vec::new();
+    coverage.write_annotated(&mut out, source).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "        1:    1: var a = 1;\n        1:    2: -nil;\n        -:    3: \n    #####:    4: print a;\n"
+    );
+}
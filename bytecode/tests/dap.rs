@@ -0,0 +1,222 @@
+mod common;
+
+use std::fs;
+use std::io;
+use std::io::Write;
+
+use serde_json::Value as Json;
+use serde_json::json;
+
+use common::Output;
+
+const SCRIPT: &str = "\
+var a = 1;
+print a;
+a = a + 1;
+print a;
+";
+
+/// Play the `requests` transcript against a server debugging `SCRIPT`
+/// and return everything it sent back.
+fn session(name: &str, requests: impl Fn(&str) -> Vec<Json>) -> Vec<Json> {
+    let path = std::env::temp_dir().join(format!("lox-dap-{name}.lox"));
+    fs::write(&path, SCRIPT).unwrap();
+
+    let mut input = Vec::new();
+    for (seq, mut request) in (1..).zip(requests(path.to_str().unwrap())) {
+        request["seq"] = json!(seq);
+        request["type"] = json!("request");
+        let body = request.to_string();
+        write!(input, "Content-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+    }
+    let out = Output::default();
+    bytecode::dap::serve(io::Cursor::new(input), out.clone()).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let out = out.text();
+    let mut messages = Vec::new();
+    let mut rest = out.as_bytes();
+    while !rest.is_empty() {
+        let text = std::str::from_utf8(rest).unwrap();
+        let (header, body) = text.split_once("\r\n\r\n").unwrap();
+        let length: usize = header
+            .strip_prefix("Content-Length: ")
+            .unwrap()
+            .parse()
+            .unwrap();
+        messages.push(serde_json::from_str(&body[..length]).unwrap());
+        rest = &body.as_bytes()[length..];
+    }
+    messages
+}
+
+/// One line per message: the response's command or the event's name,
+/// and what matters most about it.
+fn summary(messages: &[Json]) -> Vec<String> {
+    messages
+        .iter()
+        .map(|m| match m["type"].as_str().unwrap() {
+            "response" if m["success"] == json!(false) => {
+                format!(
+                    "error {}: {}",
+                    m["command"].as_str().unwrap(),
+                    m["message"].as_str().unwrap()
+                )
+            }
+            "response" => format!("response {}", m["command"].as_str().unwrap()),
+            _ => match m["event"].as_str().unwrap() {
+                "stopped" => format!("stopped {}", m["body"]["reason"].as_str().unwrap()),
+                "output" => format!("output {:?}", m["body"]["output"].as_str().unwrap()),
+                "exited" => format!("exited {}", m["body"]["exitCode"]),
+                event => event.to_string(),
+            },
+        })
+        .collect()
+}
+
+fn response<'a>(messages: &'a [Json], command: &str) -> &'a Json {
+    messages
+        .iter()
+        .find(|m| m["type"] == "response" && m["command"] == command)
+        .unwrap()
+}
+
+fn launch(program: &str, stop_on_entry: bool) -> Vec<Json> {
+    vec![
+        json!({ "command": "initialize", "arguments": { "adapterID": "lox" } }),
+        json!({ "command": "launch", "arguments": { "program": program, "stopOnEntry": stop_on_entry } }),
+    ]
+}
+
+#[test]
+fn runs_to_a_breakpoint_and_inspects() {
+    let messages = session("breakpoint", |program| {
+        let mut requests = launch(program, false);
+        requests.extend([
+            json!({ "command": "setBreakpoints", "arguments": {
+                "source": { "path": program },
+                "breakpoints": [{ "line": 3 }],
+            } }),
+            json!({ "command": "configurationDone" }),
+            json!({ "command": "threads" }),
+            json!({ "command": "stackTrace", "arguments": { "threadId": 1 } }),
+            json!({ "command": "scopes", "arguments": { "frameId": 0 } }),
+            json!({ "command": "variables", "arguments": { "variablesReference": 1 } }),
+            json!({ "command": "continue", "arguments": { "threadId": 1 } }),
+            json!({ "command": "disconnect" }),
+        ]);
+        requests
+    });
+    assert_eq!(
+        summary(&messages),
+        [
+            "response initialize",
+            "initialized",
+            "response launch",
+            "response setBreakpoints",
+            "response configurationDone",
+            "output \"1\\n\"",
+            "stopped breakpoint",
+            "response threads",
+            "response stackTrace",
+            "response scopes",
+            "response variables",
+            "response continue",
+            "output \"2\\n\"",
+            "exited 0",
+            "terminated",
+            "response disconnect",
+        ]
+    );
+
+    let frames = &response(&messages, "stackTrace")["body"]["stackFrames"];
+    assert_eq!(frames[0]["name"], "script");
+    assert_eq!(frames[0]["line"], 3);
+    assert!(
+        frames[0]["source"]["path"]
+            .as_str()
+            .unwrap()
+            .ends_with("lox-dap-breakpoint.lox")
+    );
+    assert_eq!(
+        response(&messages, "variables")["body"]["variables"],
+        json!([{ "name": "a", "value": "1", "variablesReference": 0 }])
+    );
+    assert_eq!(
+        response(&messages, "setBreakpoints")["body"]["breakpoints"][0]["verified"],
+        true
+    );
+}
+
+#[test]
+fn steps_from_entry() {
+    let messages = session("step", |program| {
+        let mut requests = launch(program, true);
+        requests.extend([
+            json!({ "command": "configurationDone" }),
+            json!({ "command": "next", "arguments": { "threadId": 1 } }),
+            json!({ "command": "stepIn", "arguments": { "threadId": 1 } }),
+            json!({ "command": "stackTrace", "arguments": { "threadId": 1 } }),
+            json!({ "command": "stepOut", "arguments": { "threadId": 1 } }),
+        ]);
+        requests
+    });
+    assert_eq!(
+        summary(&messages),
+        [
+            "response initialize",
+            "initialized",
+            "response launch",
+            "response configurationDone",
+            "stopped entry",
+            "response next",
+            "stopped step",
+            "response stepIn",
+            "output \"1\\n\"",
+            "stopped step",
+            "response stackTrace",
+            "response stepOut",
+            "output \"2\\n\"",
+            "exited 0",
+            "terminated",
+        ]
+    );
+    let frames = &response(&messages, "stackTrace")["body"]["stackFrames"];
+    assert_eq!(frames[0]["line"], 3);
+}
+
+#[test]
+fn disconnect_while_paused_stops_the_script() {
+    let messages = session("disconnect", |program| {
+        let mut requests = launch(program, true);
+        requests.extend([
+            json!({ "command": "configurationDone" }),
+            json!({ "command": "disconnect" }),
+            json!({ "command": "threads" }),
+        ]);
+        requests
+    });
+    assert_eq!(
+        summary(&messages)[3..],
+        [
+            "response configurationDone",
+            "stopped entry",
+            "response disconnect"
+        ]
+    );
+}
+
+#[test]
+fn rejects_requests_it_cannot_answer() {
+    let messages = session("errors", |_| {
+        vec![
+            json!({ "command": "stackTrace", "arguments": { "threadId": 1 } }),
+            json!({ "command": "launch", "arguments": { "program": "/no/such/file.lox" } }),
+            json!({ "command": "evaluate", "arguments": { "expression": "a" } }),
+        ]
+    });
+    let summary = summary(&messages);
+    assert_eq!(summary[0], "error stackTrace: The script isn't paused.");
+    assert!(summary[1].starts_with("error launch: Can't read /no/such/file.lox: "));
+    assert_eq!(summary[2], "error evaluate: Unsupported: evaluate.");
+}
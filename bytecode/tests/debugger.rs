@@ -0,0 +1,109 @@
+mod common;
+
+use std::io;
+
+use bytecode::compiler::compile;
+use bytecode::debugger::Breakpoint;
+use bytecode::debugger::Console;
+use bytecode::debugger::Debugger;
+use bytecode::error::LoxError;
+use bytecode::lox::Lox;
+
+use common::Output;
+
+const SCRIPT: &str = "\
+var a = 1;
+a = a + 1;
+nested();
+print a;
+";
+
+/// Run `SCRIPT` under a console fed `commands`, and return what the
+/// console printed along with the result of the run.
+fn debug(
+    commands: &'static str,
+    setup: impl FnOnce(&mut Debugger),
+) -> (String, Result<(), LoxError>) {
+    let out = Output::default();
+    let console = Console::new(commands.as_bytes(), out.clone(), SCRIPT);
+    let mut debugger = Debugger::new(Box::new(console));
+    setup(&mut debugger);
+
+    let mut lox = Lox::new();
+    lox.set_output(io::sink());
+    lox.define_native("nested", 0, |vm, _| {
+        vm.run(&compile("var b = 2;\nb = b * 2;")?)
+    });
+    lox.vm().attach_debugger(debugger);
+    let result = lox.run(SCRIPT);
+    let text = out.text();
+    (text, result)
+}
+
+#[test]
+fn stops_on_entry_and_steps_over_calls() {
+    let (out, result) = debug("n\nn\nn\nc\n", |_| {});
+    result.unwrap();
+    assert_eq!(
+        out,
+        "Stopped at entry in script\n    1 | var a = 1;\n(lox) \
+         Stopped in script\n    2 | a = a + 1;\n(lox) \
+         Stopped in script\n    3 | nested();\n(lox) \
+         Stopped in script\n    4 | print a;\n(lox) "
+    );
+}
+
+#[test]
+fn steps_into_and_out_of_calls() {
+    let (out, result) = debug("b 3\nc\ns\nbt\nfinish\nbt\nc\n", |_| {});
+    result.unwrap();
+    assert!(out.contains("Breakpoint in script\n    3 | nested();\n"));
+    // lines of the nested chunk are its own
+    assert!(out.contains("(lox) Stopped in script\n    1 | var a = 1;\n"));
+    assert!(out.contains("#0 script, line 1\n#1 nested\n#2 script, line 3\n"));
+    assert!(out.ends_with("(lox) #0 script, line 4\n(lox) "));
+}
+
+#[test]
+fn breaks_on_lines_and_functions() {
+    let (out, result) = debug("bt\nc\np a\nc\n", |debugger| {
+        debugger.set_stop_on_entry(false);
+        debugger.breakpoints_mut().push(Breakpoint::parse("nested"));
+        debugger.breakpoints_mut().push(Breakpoint::Line(4));
+    });
+    result.unwrap();
+    assert_eq!(
+        out,
+        "Breakpoint in nested\n    3 | nested();\n(lox) \
+         #0 nested\n#1 script, line 3\n(lox) \
+         Breakpoint in script\n    4 | print a;\n(lox) a = 2\n(lox) "
+    );
+}
+
+#[test]
+fn shows_and_edits_breakpoints() {
+    let (out, _) = debug(
+        "b 2\nb nested\ndelete 2\ndelete 7\nbreakpoints\nq\n",
+        |_| {},
+    );
+    assert!(out.contains("No breakpoint at 7.\n(lox) function nested\n(lox) "));
+}
+
+#[test]
+fn prints_globals() {
+    let (out, _) = debug("b 4\nc\nglobals\np b\np nope\nq\n", |_| {});
+    assert!(out.contains("(lox) a = 2\nb = 4\n(lox) b = 4\n(lox) No variable 'nope'.\n"));
+}
+
+#[test]
+fn quit_stops_the_script() {
+    let (out, result) = debug("n\nq\n", |_| {});
+    assert!(matches!(result, Err(LoxError::Interrupted { line: 2 })));
+    assert!(out.ends_with("    2 | a = a + 1;\n(lox) "));
+}
+
+#[test]
+fn end_of_input_runs_to_the_end() {
+    let (_, result) = debug("", |_| {});
+    result.unwrap();
+}
@@ -0,0 +1,154 @@
+use std::sync::atomic::Ordering;
+
+use bytecode::compiler::compile;
+use bytecode::error::LoxError;
+use bytecode::error::Result;
+use bytecode::limits::Limit;
+use bytecode::limits::Limits;
+use bytecode::lox::Lox;
+use bytecode::value::Value;
+use bytecode::vm::VM;
+
+/// A script that runs `n` increments of the global `a`.
+fn counting(n: usize) -> String {
+    // no constants in the loop body, which would soon run out
+    let mut source = String::from("var one = 1;\nvar a = 0;\n");
+    for _ in 0..n {
+        source.push_str("a = a + one;\n");
+    }
+    source
+}
+
+fn recurse(vm: &mut VM, _args: &[Value]) -> Result<Value> {
+    let chunk = compile("recurse();")?;
+    vm.run(&chunk)
+}
+
+#[test]
+fn stops_after_the_instruction_limit() {
+    let mut lox = Lox::new();
+    lox.set_limits(Limits {
+        max_instructions: Some(100),
+        ..Limits::default()
+    });
+    lox.run(&counting(10)).unwrap();
+
+    let error = lox.run(&counting(1000)).unwrap_err();
+    assert!(matches!(
+        error,
+        LoxError::LimitExceeded {
+            limit: Limit::Instructions,
+            ..
+        }
+    ));
+    assert!(
+        error
+            .to_string()
+            .starts_with("Instruction limit exceeded.\n[line ")
+    );
+}
+
+#[test]
+fn instruction_limit_is_exact() {
+    let mut lox = Lox::new();
+    // `var a = 0;` is two instructions, plus Nil and Return at the end
+    lox.set_limits(Limits {
+        max_instructions: Some(4),
+        ..Limits::default()
+    });
+    lox.run("var a = 0;").unwrap();
+    lox.set_limits(Limits {
+        max_instructions: Some(3),
+        ..Limits::default()
+    });
+    assert!(lox.run("var a = 0;").is_err());
+}
+
+#[test]
+fn interrupt_flag_stops_the_script_and_resets() {
+    let mut lox = Lox::new();
+    let flag = lox.interrupt_flag();
+    flag.store(true, Ordering::Relaxed);
+
+    let error = lox.run("var a = 1;").unwrap_err();
+    assert!(matches!(error, LoxError::Interrupted { .. }));
+    assert_eq!(lox.get::<f64>("a"), None);
+
+    assert!(!flag.load(Ordering::Relaxed));
+    lox.run("var a = 1;").unwrap();
+    assert_eq!(lox.get::<f64>("a"), Some(1.0));
+}
+
+#[test]
+fn interrupt_raised_while_running() {
+    let mut lox = Lox::new();
+    lox.define_native("stop", 0, |vm, _| {
+        vm.interrupt_flag().store(true, Ordering::Relaxed);
+        Ok(Value::nil())
+    });
+
+    let source = format!("stop();\n{}", counting(5000));
+    let error = lox.run(&source).unwrap_err();
+    assert!(matches!(error, LoxError::Interrupted { .. }));
+    assert!(lox.get::<f64>("a").unwrap() < 5000.0);
+}
+
+#[test]
+fn stops_at_the_heap_limit() {
+    let mut lox = Lox::new();
+    lox.set_limits(Limits {
+        max_heap_bytes: Some(1024),
+        ..Limits::default()
+    });
+    // redefining a global doesn't allocate
+    lox.run(&"var a = 1;\n".repeat(100)).unwrap();
+
+    let mut source = String::new();
+    for i in 0..100 {
+        source.push_str(&format!("var global{i} = {i};\n"));
+    }
+    let error = lox.run(&source).unwrap_err();
+    assert!(matches!(
+        error,
+        LoxError::LimitExceeded {
+            limit: Limit::HeapBytes,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn stops_runaway_recursion_through_natives() {
+    let mut lox = Lox::new();
+    lox.define_native("recurse", 0, recurse);
+    let error = lox.run("print 1;\nrecurse();").unwrap_err();
+    assert!(matches!(
+        error,
+        LoxError::LimitExceeded {
+            limit: Limit::CallDepth,
+            line: 2
+        }
+    ));
+
+    lox.run("var b = 2;").unwrap();
+    assert_eq!(lox.get::<f64>("b"), Some(2.0));
+}
+
+#[test]
+fn call_depth_limit_is_configurable() {
+    let mut lox = Lox::new();
+    lox.set_limits(Limits {
+        max_call_depth: Some(3),
+        ..Limits::default()
+    });
+    lox.define_native("depth", 1, |vm, args| {
+        let n = args[0].as_number().unwrap();
+        if n == 0.0 {
+            return Ok(Value::number(0.0));
+        }
+        let chunk = compile(&format!("depth({});", n - 1.0))?;
+        vm.run(&chunk)
+    });
+    lox.run("depth(2);").unwrap();
+    assert!(lox.run("depth(3);").is_err());
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use bytecode::error::LoxError;
+use bytecode::lox::Lox;
+use bytecode::value::Value;
+
+use common::Output;
+
+fn session() -> (Lox, Output) {
+    let out = Output::default();
+    let mut lox = Lox::new();
+    lox.set_output(out.clone());
+    (lox, out)
+}
+
+#[test]
+fn prints_to_the_given_writer() {
+    let (mut lox, out) = session();
+    lox.run("print 1 + 2 * 3; print !(1 < 2); print nil;").unwrap();
+    assert_eq!(out.text(), "7\nfalse\nnil\n");
+}
+
+#[test]
+fn keeps_globals_between_runs() {
+    let (mut lox, out) = session();
+    lox.run("var a = 1;").unwrap();
+    lox.run("a = a + 1; print a;").unwrap();
+    assert_eq!(out.text(), "2\n");
+    assert_eq!(lox.get::<f64>("a"), Some(2.0));
+}
+
+#[test]
+fn host_reads_and_writes_globals() {
+    let (mut lox, out) = session();
+    lox.set("x", 20.0);
+    lox.set("flag", true);
+    lox.run("var y = x * 2 + 2; print flag == true;").unwrap();
+    assert_eq!(lox.get::<f64>("y"), Some(42.0));
+    assert_eq!(lox.get::<bool>("y"), None);
+    assert_eq!(lox.get::<f64>("missing"), None);
+    assert_eq!(out.text(), "true\n");
+}
+
+#[test]
+fn calls_functions_from_rust() {
+    let (mut lox, _) = session();
+    lox.define_native("add", 2, |_, args| {
+        let sum = args[0].as_number().unwrap() + args[1].as_number().unwrap();
+        Ok(Value::number(sum))
+    });
+    let result = lox.call("add", &[Value::number(1.0), Value::number(2.0)]);
+    assert_eq!(result.unwrap(), Value::number(3.0));
+    assert_eq!(lox.call("max", &[1.0.into(), 5.0.into()]).unwrap(), Value::number(5.0));
+    assert!(lox.call("nope", &[]).is_err());
+}
+
+#[test]
+fn lox_calls_natives() {
+    let (mut lox, out) = session();
+    lox.run("print sqrt(16) + max(1, 2);").unwrap();
+    assert_eq!(out.text(), "6\n");
+}
+
+#[test]
+fn evaluates_expressions_to_strings() {
+    let (mut lox, out) = session();
+    lox.run("var x = 3;").unwrap();
+    assert_eq!(lox.eval_to_string("x * 2 + 0.5").unwrap(), "6.5");
+    assert_eq!(lox.eval_to_string("x > 2;").unwrap(), "true");
+    assert_eq!(lox.eval_to_string("nil").unwrap(), "nil");
+    assert_eq!(lox.eval_to_string("1 / 0").unwrap(), "inf");
+    assert_eq!(lox.eval_to_string("sqrt").unwrap(), "<native fn>");
+    assert!(lox.eval_to_string("var y = 1;").is_err());
+    assert!(lox.eval_to_string("-nil").is_err());
+    assert_eq!(out.text(), "");
+}
+
+#[test]
+fn reports_compile_errors() {
+    let (mut lox, _) = session();
+    let Err(LoxError::CompileError(errors)) = lox.run("print 1 +;\nvar = 2;\n1 = 2;") else {
+        panic!("expected a compile error");
+    };
+    assert_eq!(
+        errors,
+        [
+            "[line 1] Error at ';': Expect expression.",
+            "[line 2] Error at '=': Expect variable name.",
+            "[line 3] Error at '=': Invalid assignment target.",
+        ]
+    );
+}
+
+#[test]
+fn reports_runtime_errors() {
+    let (mut lox, _) = session();
+    let err = lox.run("var a = 1;\nprint b;").unwrap_err();
+    assert_eq!(err.to_string(), "Undefined variable 'b'.\n[line 2] in script");
+
+    let err = lox.run("undefined = 1;").unwrap_err();
+    assert_eq!(err.to_string(), "Undefined variable 'undefined'.\n[line 1] in script");
+
+    let err = lox.run("print -true;").unwrap_err();
+    assert_eq!(err.to_string(), "Operand must be a number.\n[line 1] in script");
+
+    // the session is still usable afterwards
+    lox.run("print a;").unwrap();
+}
@@ -0,0 +1,68 @@
+use bytecode::chunk::Chunk;
+use bytecode::error::LoxError;
+use bytecode::opcode::OpCode;
+use bytecode::value::Value;
+use bytecode::vm::VM;
+
+/// `callee(args...)`, with the callee and arguments as constants.
+fn call_chunk(callee: Value, args: &[Value]) -> Chunk {
+    let mut chunk = Chunk::new("call");
+    chunk.emit_constant(callee, 1);
+    for &arg in args {
+        chunk.emit_constant(arg, 1);
+    }
+    chunk.emit_op(OpCode::Call, 2);
+    chunk.emit_byte(args.len() as u8, 2);
+    chunk.emit_op(OpCode::Return, 2);
+    chunk
+}
+
+#[test]
+fn calls_default_natives() {
+    let mut vm = VM::new();
+    for name in ["clock", "abs", "ceil", "floor", "round", "sqrt", "min", "max", "pow"] {
+        assert!(vm.global(name).is_some(), "missing native {name}");
+    }
+    let sqrt = vm.global("sqrt").unwrap();
+    vm.run(&call_chunk(sqrt, &[Value::number(9.0)])).unwrap();
+}
+
+#[test]
+fn checks_arity() {
+    let mut vm = VM::new();
+    let pow = vm.global("pow").unwrap();
+    let err = vm.run(&call_chunk(pow, &[Value::number(2.0)])).unwrap_err();
+    assert_eq!(err.to_string(), "Expected 2 arguments but got 1.\n[line 2] in script");
+}
+
+#[test]
+fn natives_raise_runtime_errors() {
+    let mut vm = VM::new();
+    let sqrt = vm.global("sqrt").unwrap();
+    let err = vm.run(&call_chunk(sqrt, &[Value::nil()])).unwrap_err();
+    assert_eq!(err.to_string(), "Argument must be a number.\n[line 2] in script");
+
+    vm.define_native("fail", 0, |_, _| Err(LoxError::runtime("Nope.")));
+    let fail = vm.global("fail").unwrap();
+    let err = vm.run(&call_chunk(fail, &[])).unwrap_err();
+    assert_eq!(err.to_string(), "Nope.\n[line 2] in script");
+}
+
+#[test]
+fn host_natives_get_their_arguments() {
+    let mut vm = VM::new();
+    vm.define_native("check", 2, |_, args| {
+        assert_eq!(args, [Value::number(1.0), Value::bool(true)]);
+        Ok(Value::nil())
+    });
+    let check = vm.global("check").unwrap();
+    vm.run(&call_chunk(check, &[Value::number(1.0), Value::bool(true)]))
+        .unwrap();
+}
+
+#[test]
+fn only_functions_are_callable() {
+    let mut vm = VM::new();
+    let err = vm.run(&call_chunk(Value::number(1.0), &[])).unwrap_err();
+    assert_eq!(err.to_string(), "Can only call functions and classes.\n[line 2] in script");
+}
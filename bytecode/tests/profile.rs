@@ -0,0 +1,86 @@
+use bytecode::compiler::compile;
+use bytecode::lox::Lox;
+use bytecode::opcode::OpCode;
+use bytecode::value::Value;
+
+fn profiled(source: &str) -> bytecode::profile::Profile {
+    let mut lox = Lox::new();
+    lox.set_output(std::io::sink());
+    lox.vm().start_profile(false);
+    lox.run(source).unwrap();
+    lox.vm().take_profile().unwrap()
+}
+
+#[test]
+fn counts_opcodes() {
+    let profile = profiled("print 1; print 2;");
+    let counts: Vec<_> = profile
+        .opcodes()
+        .into_iter()
+        .map(|(op, count)| (op.name(), count))
+        .collect();
+    assert_eq!(
+        counts,
+        [("constant", 2), ("print", 2), ("return", 1), ("nil", 1)]
+    );
+    assert_eq!(profile.instructions(), 6);
+}
+
+#[test]
+fn counts_lines() {
+    let profile = profiled("var a = 1;\na = a + 1;\na = a + 1;\nprint a;");
+    // get, add_constant, set and pop on each of lines 2 and 3, and
+    // get, print, nil and return on the last
+    assert_eq!(profile.lines(), [(2, 4), (3, 4), (4, 4), (1, 2)]);
+}
+
+#[test]
+fn counts_calls_per_function() {
+    let profile = profiled("sqrt(4); sqrt(9); abs(-1);");
+    let functions: Vec<_> = profile
+        .functions()
+        .into_iter()
+        .map(|(name, stats)| (name, stats.calls))
+        .collect();
+    assert_eq!(functions, [("script", 1), ("abs", 1), ("sqrt", 2)]);
+}
+
+#[test]
+fn writes_collapsed_stacks() {
+    let mut lox = Lox::new();
+    lox.define_native("nested", 0, |vm, _| vm.run(&compile("clock();")?));
+    lox.vm().start_profile(false);
+    lox.run("nested();\nclock();").unwrap();
+    let profile = lox.vm().take_profile().unwrap();
+
+    let mut out = Vec::new();
+    profile.write_collapsed(&mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "script 8\n\
+         script;clock 1\n\
+         script;nested 1\n\
+         script;nested;script 5\n\
+         script;nested;script;clock 1\n"
+    );
+}
+
+#[test]
+fn keeps_what_ran_before_an_error() {
+    let mut lox = Lox::new();
+    lox.vm().start_profile(true);
+    assert!(lox.run("clock();\n-nil;").is_err());
+    let profile = lox.vm().take_profile().unwrap();
+    assert!(profile.opcodes().contains(&(OpCode::Negate, 1)));
+    assert_eq!(profile.functions().len(), 2);
+}
+
+#[test]
+fn profiles_calls_from_the_host() {
+    let mut lox = Lox::new();
+    lox.vm().start_profile(false);
+    lox.call("max", &[Value::number(1.0), Value::number(2.0)])
+        .unwrap();
+    let profile = lox.vm().take_profile().unwrap();
+    assert_eq!(profile.functions()[0].0, "max");
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use std::fs;
+
+use bytecode::repl::Prompt;
+use bytecode::repl::Repl;
+
+use common::Output;
+
+fn repl() -> (Repl, Output) {
+    let out = Output::default();
+    (Repl::new(out.clone()), out)
+}
+
+#[test]
+fn keeps_state_between_entries() {
+    let (mut repl, out) = repl();
+    assert_eq!(repl.feed("var a = 1;"), Prompt::Ready);
+    assert_eq!(repl.feed("print a + 1;"), Prompt::Ready);
+    assert_eq!(out.take(), "2\n");
+}
+
+#[test]
+fn prints_bare_expressions() {
+    let (mut repl, out) = repl();
+    repl.feed("1 + 2");
+    repl.feed("var a = 3;");
+    repl.feed("a * 2;");
+    repl.feed("a = 4");
+    repl.feed("nil");
+    assert_eq!(out.take(), "3\n6\n4\nnil\n");
+}
+
+#[test]
+fn asks_for_more_until_the_entry_is_complete() {
+    let (mut repl, out) = repl();
+    assert_eq!(repl.feed("var a ="), Prompt::Continue);
+    assert_eq!(repl.feed("  1"), Prompt::Continue);
+    assert_eq!(repl.feed(";"), Prompt::Ready);
+    assert_eq!(repl.feed("print (a +"), Prompt::Continue);
+    assert_eq!(repl.feed("2)"), Prompt::Continue);
+    assert_eq!(repl.feed(";"), Prompt::Ready);
+    assert_eq!(repl.feed("1 +"), Prompt::Continue);
+    assert_eq!(repl.feed("1"), Prompt::Ready);
+    assert_eq!(out.take(), "3\n2\n");
+}
+
+#[test]
+fn blank_line_ends_an_unfinished_entry() {
+    let (mut repl, out) = repl();
+    assert_eq!(repl.feed("print 1"), Prompt::Continue);
+    assert_eq!(repl.feed(""), Prompt::Ready);
+    assert_eq!(out.take(), "[line 2] Error at end: Expect ';' after value.\n");
+}
+
+#[test]
+fn reports_errors_and_carries_on() {
+    let (mut repl, out) = repl();
+    repl.feed("print -nil;");
+    repl.feed(")");
+    repl.feed("print 1;");
+    assert_eq!(
+        out.take(),
+        "Operand must be a number.\n[line 1] in script\n\
+         [line 1] Error at ')': Expect expression.\n\
+         1\n"
+    );
+}
+
+#[test]
+fn cancel_drops_the_unfinished_entry() {
+    let (mut repl, out) = repl();
+    assert_eq!(repl.feed("print 1"), Prompt::Continue);
+    repl.cancel();
+    assert_eq!(repl.feed("2"), Prompt::Ready);
+    assert_eq!(out.take(), "2\n");
+}
+
+#[test]
+fn meta_commands() {
+    let (mut repl, out) = repl();
+    repl.feed("var b = 2;");
+    repl.feed("var a = 1;");
+    repl.feed(":globals");
+    assert_eq!(out.take(), "a = 1\nb = 2\n");
+
+    repl.feed(":dis");
+    assert_eq!(
+        out.take(),
+        "== script ==\n\
+         0000    1 constant            0 '1'\n\
+         0002    | define_global       0 'a'\n\
+         0004    | nil\n\
+         0005    | return\n"
+    );
+    repl.feed(":dis 1 + 2");
+    assert!(out.take().contains("add_constant"));
+
+    repl.feed(":reset");
+    repl.feed(":globals");
+    repl.feed("a");
+    assert_eq!(out.take(), "Undefined variable 'a'.\n[line 1] in script\n");
+
+    repl.feed(":nope");
+    assert_eq!(out.take(), "Unknown command ':nope'; try :help.\n");
+    assert_eq!(repl.feed(":quit"), Prompt::Quit);
+}
+
+#[test]
+fn loads_files_into_the_session() {
+    let path = std::env::temp_dir().join("lox-repl-load.lox");
+    fs::write(&path, "var loaded = 42;\nprint loaded;\n").unwrap();
+    let (mut repl, out) = repl();
+    repl.feed(&format!(":load {}", path.display()));
+    repl.feed("loaded + 1");
+    fs::remove_file(&path).unwrap();
+    assert_eq!(out.take(), "42\n43\n");
+
+    repl.feed(":load /no/such/file.lox");
+    assert!(out.take().starts_with("Can't read /no/such/file.lox: "));
+}
@@ -0,0 +1,92 @@
+//! These run against whichever `Value` representation is compiled in;
+//! run them both ways with `cargo test` and
+//! `cargo test --features nan-boxing`.
+
+use bytecode::chunk::Chunk;
+use bytecode::opcode::OpCode;
+use bytecode::value::Value;
+use bytecode::vm::VM;
+
+#[test]
+fn numbers_round_trip() {
+    for n in [0.0, -0.0, 1.5, -6.2, f64::MAX, f64::MIN_POSITIVE, f64::INFINITY] {
+        let v = Value::number(n);
+        assert_eq!(v.as_number().map(f64::to_bits), Some(n.to_bits()));
+        assert!(!v.is_nil());
+        assert_eq!(v.as_bool(), None);
+    }
+}
+
+#[test]
+fn nan_is_a_number_but_not_equal_to_itself() {
+    let v = Value::number(f64::NAN);
+    assert!(v.as_number().unwrap().is_nan());
+    assert_ne!(v, v);
+}
+
+#[test]
+fn nil_and_bools() {
+    assert!(Value::nil().is_nil());
+    assert_eq!(Value::nil().as_number(), None);
+    assert_eq!(Value::nil().as_bool(), None);
+    assert_eq!(Value::bool(true).as_bool(), Some(true));
+    assert_eq!(Value::bool(false).as_bool(), Some(false));
+    assert!(!Value::bool(false).is_nil());
+    assert_eq!(Value::bool(false).as_number(), None);
+}
+
+#[test]
+fn equality() {
+    assert_eq!(Value::nil(), Value::nil());
+    assert_eq!(Value::bool(true), Value::from(true));
+    assert_eq!(Value::number(0.0), Value::number(-0.0));
+    assert_ne!(Value::bool(true), Value::bool(false));
+    assert_ne!(Value::nil(), Value::bool(false));
+    assert_ne!(Value::number(0.0), Value::bool(false));
+    assert_ne!(Value::number(0.0), Value::nil());
+}
+
+#[test]
+fn formatting() {
+    assert_eq!(Value::number(-6.2).to_string(), "-6.2");
+    assert_eq!(Value::number(2.0).to_string(), "2");
+    assert_eq!(Value::number(-0.0).to_string(), "-0");
+    assert_eq!(Value::number(f64::NAN).to_string(), "nan");
+    assert_eq!(Value::number(f64::INFINITY).to_string(), "inf");
+    assert_eq!(Value::nil().to_string(), "nil");
+    assert_eq!(Value::bool(true).to_string(), "true");
+    assert_eq!(format!("{:?}", Value::number(5.0)), "5.0");
+}
+
+#[cfg(feature = "nan-boxing")]
+#[test]
+fn nan_boxed_values_fit_in_a_word() {
+    assert_eq!(size_of::<Value>(), size_of::<u64>());
+}
+
+#[test]
+fn vm_runs_arithmetic() {
+    let mut chunk = Chunk::new("arithmetic");
+    chunk.emit_constant(Value::number(1.2), 1);
+    chunk.emit_op(OpCode::Negate, 1);
+    chunk.emit_constant(Value::number(5.0), 1);
+    chunk.emit_op(OpCode::Subtract, 1);
+    chunk.emit_op(OpCode::Return, 1);
+    assert!(VM::new().run(&chunk).is_ok());
+}
+
+#[test]
+fn vm_rejects_non_number_operands() {
+    let mut chunk = Chunk::new("negate nil");
+    chunk.emit_constant(Value::nil(), 1);
+    chunk.emit_op(OpCode::Negate, 1);
+    chunk.emit_op(OpCode::Return, 1);
+    assert!(VM::new().run(&chunk).is_err());
+
+    let mut chunk = Chunk::new("add bool");
+    chunk.emit_constant(Value::number(1.0), 1);
+    chunk.emit_constant(Value::bool(true), 1);
+    chunk.emit_op(OpCode::Add, 1);
+    chunk.emit_op(OpCode::Return, 1);
+    assert!(VM::new().run(&chunk).is_err());
+}
@@ -0,0 +1,123 @@
+use bytecode::chunk::Chunk;
+use bytecode::opcode::OpCode;
+use bytecode::stack::STACK_MAX;
+use bytecode::value::Value;
+use bytecode::vm::VM;
+
+#[test]
+fn rejects_chunk_without_return() {
+    let mut chunk = Chunk::new("no return");
+    chunk.emit_constant(Value::number(1.0), 1);
+    assert!(chunk.verify().is_err());
+    assert!(VM::new().run(&chunk).is_err());
+}
+
+#[test]
+fn rejects_truncated_and_unknown_instructions() {
+    let mut chunk = Chunk::new("truncated");
+    chunk.emit_op(OpCode::Return, 1);
+    chunk.emit_op(OpCode::Constant, 1);
+    assert!(chunk.verify().is_err());
+
+    let mut chunk = Chunk::new("bad constant");
+    chunk.emit_op(OpCode::Constant, 1);
+    chunk.emit_byte(3, 1);
+    chunk.emit_op(OpCode::Return, 1);
+    assert!(chunk.verify().is_err());
+
+    let mut chunk = Chunk::new("unknown opcode");
+    chunk.emit_byte(0xff, 1);
+    chunk.emit_op(OpCode::Return, 1);
+    assert!(chunk.verify().is_err());
+}
+
+#[test]
+fn counts_dispatches() {
+    let mut chunk = Chunk::new("count");
+    chunk.emit_constant(Value::number(1.0), 1);
+    chunk.emit_op(OpCode::Negate, 1);
+    chunk.emit_op(OpCode::Return, 1);
+    let mut vm = VM::new();
+    vm.run(&chunk).unwrap();
+    assert_eq!(vm.dispatch_count(), 3);
+}
+
+#[test]
+fn rejects_stack_underflow() {
+    let mut chunk = Chunk::new("underflow");
+    chunk.emit_constant(Value::number(1.0), 1);
+    chunk.emit_op(OpCode::Add, 1);
+    chunk.emit_op(OpCode::Return, 1);
+    assert!(chunk.verify().is_err());
+}
+
+#[test]
+fn reports_stack_overflow() {
+    let mut chunk = Chunk::new("overflow");
+    let idx = chunk.add_constant(Value::number(1.0)) as u8;
+    for _ in 0..=STACK_MAX {
+        chunk.emit_op(OpCode::Constant, 7);
+        chunk.emit_byte(idx, 7);
+    }
+    chunk.emit_op(OpCode::Return, 7);
+    assert_eq!(chunk.verify().unwrap(), STACK_MAX + 1);
+
+    let err = VM::new().run(&chunk).unwrap_err();
+    assert_eq!(err.to_string(), "Stack overflow.\n[line 7] in script");
+}
+
+#[test]
+fn reports_type_errors_with_line() {
+    let mut chunk = Chunk::new("negate nil");
+    chunk.emit_constant(Value::number(1.0), 1);
+    chunk.emit_constant(Value::nil(), 2);
+    chunk.emit_op(OpCode::Negate, 3);
+    chunk.emit_op(OpCode::Return, 3);
+    let err = VM::new().run(&chunk).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Operand must be a number.\n[line 3] in script"
+    );
+}
+
+/// `(((7 + 2) * 3) - 4) / 5`, with each right operand a constant, all
+/// on line 1 unless `split_lines`, which puts each op on its own line.
+fn constant_chain(fuse: bool, split_lines: bool) -> Chunk {
+    let mut chunk = Chunk::new("chain");
+    chunk.set_fusion(fuse);
+    chunk.emit_constant(Value::number(7.0), 1);
+    let ops = [
+        (OpCode::Add, 2.0),
+        (OpCode::Multiply, 3.0),
+        (OpCode::Subtract, 4.0),
+        (OpCode::Divide, 5.0),
+    ];
+    for (i, (op, constant)) in (1..).zip(ops) {
+        let line = if split_lines { i } else { 1 };
+        chunk.emit_constant(Value::number(constant), line);
+        chunk.emit_op(op, line + u32::from(split_lines));
+    }
+    chunk.emit_op(OpCode::Return, 9);
+    chunk
+}
+
+#[test]
+fn fused_and_unfused_code_compute_the_same_values() {
+    let fused = constant_chain(true, false);
+    let plain = constant_chain(false, false);
+    assert!(fused.code().len() < plain.code().len());
+
+    let fused_value = VM::new().run(&fused).unwrap();
+    let plain_value = VM::new().run(&plain).unwrap();
+    assert_eq!(fused_value.to_string(), "4.6");
+    assert_eq!(fused_value.to_string(), plain_value.to_string());
+}
+
+#[test]
+fn fusion_stops_at_a_line_change() {
+    // every op is on the line after its constant, so nothing fuses
+    let fused = constant_chain(true, true);
+    let plain = constant_chain(false, true);
+    assert_eq!(fused.code(), plain.code());
+    assert_eq!(VM::new().run(&fused).unwrap().to_string(), "4.6");
+}
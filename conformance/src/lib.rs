@@ -0,0 +1,307 @@
+//! Runs Lox scripts annotated the way the Crafting Interpreters test
+//! suite annotates them, and checks what an interpreter made of them.
+//!
+//! A script says what it should print with `// expect: TEXT`, one
+//! comment per line of output.  `// expect runtime error: MESSAGE`
+//! means the script stops there with that error, and `// Error ...` or
+//! `// [line N] Error ...` means it doesn't compile, with that error
+//! reported on the comment's line or on line N.  Errors only one of
+//! the book's interpreters reports are marked `[java line N]` or
+//! `[c line N]`.  A script marked `// nontest` isn't a test.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Exit status for a script that doesn't compile.
+pub const EXIT_COMPILE_ERROR: i32 = 65;
+/// Exit status for a script that stops with a runtime error.
+pub const EXIT_RUNTIME_ERROR: i32 = 70;
+
+/// Which of the book's interpreters an interpreter under test follows,
+/// for the errors only one of them reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    Java,
+    C,
+}
+
+impl Dialect {
+    fn name(self) -> &'static str {
+        match self {
+            Dialect::Java => "java",
+            Dialect::C => "c",
+        }
+    }
+}
+
+/// What a script says should happen when it runs.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Expectations {
+    /// Each line of output, with the line of the script expecting it.
+    pub output: Vec<(u32, String)>,
+    /// Compile errors, as the interpreter reports them.
+    pub compile_errors: Vec<String>,
+    /// The runtime error message and the line it happens on.
+    pub runtime_error: Option<(String, u32)>,
+}
+
+impl Expectations {
+    /// The expectations in `source`, or `None` if it isn't a test.
+    pub fn parse(source: &str, dialect: Option<Dialect>) -> Option<Self> {
+        let mut expectations = Expectations::default();
+        for (number, line) in (1..).zip(source.lines()) {
+            let Some((_, comment)) = line.split_once("// ") else {
+                continue;
+            };
+            if comment.starts_with("nontest") {
+                return None;
+            }
+            if let Some(text) = comment.strip_prefix("expect: ") {
+                expectations.output.push((number, text.to_string()));
+            } else if let Some(message) = comment.strip_prefix("expect runtime error: ") {
+                expectations.runtime_error = Some((message.to_string(), number));
+            } else if comment.starts_with("Error") {
+                let error = format!("[line {number}] {comment}");
+                expectations.compile_errors.push(error);
+            } else if let Some(error) = line_error(comment, dialect) {
+                expectations.compile_errors.push(error);
+            }
+        }
+        Some(expectations)
+    }
+
+    /// The status the interpreter should exit with.
+    pub fn exit_code(&self) -> i32 {
+        if !self.compile_errors.is_empty() {
+            EXIT_COMPILE_ERROR
+        } else if self.runtime_error.is_some() {
+            EXIT_RUNTIME_ERROR
+        } else {
+            0
+        }
+    }
+}
+
+/// A `[line N] Error ...` comment, with any dialect prefix dropped, if
+/// it applies to `dialect`.
+fn line_error(comment: &str, dialect: Option<Dialect>) -> Option<String> {
+    let rest = comment.strip_prefix('[')?;
+    let rest = match rest.split_once(" line ") {
+        Some((name, rest)) => {
+            if dialect.map(Dialect::name) != Some(name) {
+                return None;
+            }
+            rest
+        }
+        None => rest.strip_prefix("line ")?,
+    };
+    let (line, error) = rest.split_once("] ")?;
+    let line: u32 = line.parse().ok()?;
+    error
+        .starts_with("Error")
+        .then(|| format!("[line {line}] {error}"))
+}
+
+/// What an interpreter did with a script.
+#[derive(Debug, Default)]
+pub struct Run {
+    pub stdout: String,
+    pub stderr: String,
+    /// `None` if it was killed by a signal.
+    pub status: Option<i32>,
+}
+
+impl Run {
+    /// Run `script` with `interpreter`.
+    pub fn new(interpreter: &Path, script: &Path) -> io::Result<Self> {
+        let output = Command::new(interpreter).arg(script).output()?;
+        Ok(Run {
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            status: output.status.code(),
+        })
+    }
+}
+
+/// One way a run fell short of its script's expectations.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Failure {
+    /// The output, line by line, as `-` expected, `+` printed or ` `
+    /// both.
+    Output(Vec<String>),
+    MissingCompileError(String),
+    UnexpectedError(String),
+    RuntimeError {
+        expected: String,
+        actual: Option<String>,
+    },
+    RuntimeErrorLine {
+        expected: u32,
+        actual: Option<u32>,
+    },
+    ExitCode {
+        expected: i32,
+        actual: Option<i32>,
+    },
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Output(diff) => {
+                write!(f, "Output differs (- expected, + actual):")?;
+                for line in diff {
+                    write!(f, "\n  {line}")?;
+                }
+                Ok(())
+            }
+            Failure::MissingCompileError(error) => write!(f, "Missing expected error: {error}"),
+            Failure::UnexpectedError(error) => write!(f, "Unexpected error: {error}"),
+            Failure::RuntimeError { expected, actual } => match actual {
+                Some(actual) => {
+                    write!(f, "Expected runtime error '{expected}' and got '{actual}'.")
+                }
+                None => write!(f, "Expected runtime error '{expected}' and got none."),
+            },
+            Failure::RuntimeErrorLine { expected, actual } => match actual {
+                Some(actual) => write!(
+                    f,
+                    "Expected runtime error on line {expected} but was on line {actual}."
+                ),
+                None => write!(
+                    f,
+                    "Expected a stack trace for line {expected} and got none."
+                ),
+            },
+            Failure::ExitCode { expected, actual } => match actual {
+                Some(actual) => write!(f, "Expected exit code {expected} and got {actual}."),
+                None => write!(
+                    f,
+                    "Expected exit code {expected} and was killed by a signal."
+                ),
+            },
+        }
+    }
+}
+
+/// Everything about `run` that doesn't match `expected`.
+pub fn check(expected: &Expectations, run: &Run) -> Vec<Failure> {
+    let mut failures = Vec::new();
+
+    let printed: Vec<&str> = run.stdout.lines().collect();
+    let wanted: Vec<&str> = expected
+        .output
+        .iter()
+        .map(|(_, text)| text.as_str())
+        .collect();
+    if printed != wanted {
+        failures.push(Failure::Output(diff(&wanted, &printed)));
+    }
+
+    let errors: Vec<&str> = run.stderr.lines().filter(|l| !l.is_empty()).collect();
+    if let Some((message, line)) = &expected.runtime_error {
+        check_runtime_error(message, *line, &errors, &mut failures);
+    } else {
+        for error in &expected.compile_errors {
+            if !errors.contains(&error.as_str()) {
+                failures.push(Failure::MissingCompileError(error.clone()));
+            }
+        }
+        for error in errors {
+            if !expected.compile_errors.iter().any(|e| e == error) {
+                failures.push(Failure::UnexpectedError(error.to_string()));
+            }
+        }
+    }
+
+    let exit_code = expected.exit_code();
+    if run.status != Some(exit_code) {
+        failures.push(Failure::ExitCode {
+            expected: exit_code,
+            actual: run.status,
+        });
+    }
+    failures
+}
+
+/// The message comes first, then a stack trace whose first `[line N]`
+/// is where it happened.
+fn check_runtime_error(message: &str, line: u32, errors: &[&str], failures: &mut Vec<Failure>) {
+    if errors.first() != Some(&message) {
+        failures.push(Failure::RuntimeError {
+            expected: message.to_string(),
+            actual: errors.first().map(|e| e.to_string()),
+        });
+        return;
+    }
+    let actual = errors[1..].iter().find_map(|error| {
+        let (_, rest) = error.split_once("[line ")?;
+        let (number, _) = rest.split_once(']')?;
+        number.parse().ok()
+    });
+    if actual != Some(line) {
+        failures.push(Failure::RuntimeErrorLine {
+            expected: line,
+            actual,
+        });
+    }
+}
+
+/// A line diff of `expected` against `actual`, from their longest
+/// common subsequence.
+pub fn diff(expected: &[&str], actual: &[&str]) -> Vec<String> {
+    let (n, m) = (expected.len(), actual.len());
+    // common[i][j]: the longest common subsequence of expected[i..] and actual[j..]
+    let mut common = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            common[i][j] = if expected[i] == actual[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && expected[i] == actual[j] {
+            lines.push(format!("  {}", expected[i]));
+            i += 1;
+            j += 1;
+        } else if j == m || (i < n && common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(format!("- {}", expected[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+ {}", actual[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+/// Every `.lox` file under `path`, or `path` itself if it's a file, in
+/// order.
+pub fn scripts(path: &Path) -> io::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut found = Vec::new();
+    let mut entries: Vec<_> = fs::read_dir(path)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<_>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            found.extend(scripts(&entry)?);
+        } else if entry.extension().is_some_and(|e| e == "lox") {
+            found.push(entry);
+        }
+    }
+    Ok(found)
+}
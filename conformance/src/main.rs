@@ -0,0 +1,103 @@
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Parser;
+use lox_conformance::Dialect;
+use lox_conformance::Expectations;
+use lox_conformance::Failure;
+use lox_conformance::Run;
+use lox_conformance::check;
+use lox_conformance::scripts;
+
+/// Run annotated Lox scripts through an interpreter and report which
+/// of them did what they say they should.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    /// The interpreter, which is run with each script as its argument
+    interpreter: PathBuf,
+    /// Scripts, or directories to search for `.lox` files
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+    /// Also expect the errors marked for one of the book's
+    /// interpreters: `java` or `c`
+    #[arg(long, value_parser = parse_dialect)]
+    dialect: Option<Dialect>,
+    /// Only run scripts whose path contains this
+    #[arg(long)]
+    filter: Option<String>,
+    /// Don't list the scripts that pass
+    #[arg(short, long)]
+    quiet: bool,
+}
+
+fn parse_dialect(name: &str) -> Result<Dialect, String> {
+    match name {
+        "java" => Ok(Dialect::Java),
+        "c" => Ok(Dialect::C),
+        _ => Err(format!("expected 'java' or 'c', not '{name}'")),
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let mut passed = 0;
+    let mut failed = 0;
+    for path in &args.paths {
+        let scripts = match scripts(path) {
+            Ok(scripts) => scripts,
+            Err(e) => {
+                eprintln!("Can't read {}: {e}.", path.display());
+                return ExitCode::FAILURE;
+            }
+        };
+        for script in scripts {
+            if let Some(filter) = &args.filter
+                && !script.to_string_lossy().contains(filter.as_str())
+            {
+                continue;
+            }
+            let failures = match test(&args, &script) {
+                Ok(Some(failures)) => failures,
+                Ok(None) => continue,
+                Err(e) => {
+                    eprintln!("Can't run {}: {e}.", script.display());
+                    return ExitCode::FAILURE;
+                }
+            };
+            if failures.is_empty() {
+                passed += 1;
+                if !args.quiet {
+                    println!("PASS {}", script.display());
+                }
+            } else {
+                failed += 1;
+                println!("FAIL {}", script.display());
+                for failure in failures {
+                    for line in failure.to_string().lines() {
+                        println!("    {line}");
+                    }
+                }
+            }
+        }
+    }
+
+    println!("{passed} passed, {failed} failed.");
+    if failed > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// How `script` fails, or `None` if it isn't a test.
+fn test(args: &Args, script: &Path) -> std::io::Result<Option<Vec<Failure>>> {
+    let source = fs::read_to_string(script)?;
+    let Some(expectations) = Expectations::parse(&source, args.dialect) else {
+        return Ok(None);
+    };
+    let run = Run::new(&args.interpreter, script)?;
+    Ok(Some(check(&expectations, &run)))
+}
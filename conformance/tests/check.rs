@@ -0,0 +1,156 @@
+use lox_conformance::Dialect;
+use lox_conformance::Expectations;
+use lox_conformance::Failure;
+use lox_conformance::Run;
+use lox_conformance::check;
+use lox_conformance::diff;
+
+fn run(stdout: &str, stderr: &str, status: i32) -> Run {
+    Run {
+        stdout: stdout.to_string(),
+        stderr: stderr.to_string(),
+        status: Some(status),
+    }
+}
+
+#[test]
+fn parses_expected_output() {
+    let expectations =
+        Expectations::parse("print 1; // expect: 1\n\nprint nil; // expect: nil", None);
+    let expectations = expectations.unwrap();
+    assert_eq!(
+        expectations.output,
+        [(1, "1".to_string()), (3, "nil".to_string())]
+    );
+    assert_eq!(expectations.exit_code(), 0);
+}
+
+#[test]
+fn parses_errors() {
+    let source = "\
+var a = 1;
+-nil; // expect runtime error: Operand must be a number.
+";
+    let expectations = Expectations::parse(source, None).unwrap();
+    assert_eq!(
+        expectations.runtime_error,
+        Some(("Operand must be a number.".to_string(), 2))
+    );
+    assert_eq!(expectations.exit_code(), 70);
+
+    let source = "\
+// [line 3] Error at end: Expect expression.
+var a = ; // Error at ';': Expect expression.
+print";
+    let expectations = Expectations::parse(source, None).unwrap();
+    assert_eq!(
+        expectations.compile_errors,
+        [
+            "[line 3] Error at end: Expect expression.",
+            "[line 2] Error at ';': Expect expression.",
+        ]
+    );
+    assert_eq!(expectations.exit_code(), 65);
+}
+
+#[test]
+fn dialect_errors_only_for_that_dialect() {
+    let source = "\
+// [java line 2] Error at '=': Java only.
+// [c line 2] Error at '=': C only.
+";
+    let errors = |dialect| Expectations::parse(source, dialect).unwrap().compile_errors;
+    assert!(errors(None).is_empty());
+    assert_eq!(
+        errors(Some(Dialect::Java)),
+        ["[line 2] Error at '=': Java only."]
+    );
+    assert_eq!(errors(Some(Dialect::C)), ["[line 2] Error at '=': C only."]);
+}
+
+#[test]
+fn nontests_are_skipped() {
+    assert_eq!(
+        Expectations::parse("// nontest\nprint 1; // expect: 1", None),
+        None
+    );
+}
+
+#[test]
+fn passing_runs() {
+    let expectations = Expectations::parse(
+        "print 1; // expect: 1\n-nil; // expect runtime error: Bad.",
+        None,
+    )
+    .unwrap();
+    let failures = check(&expectations, &run("1\n", "Bad.\n[line 2] in script\n", 70));
+    assert_eq!(failures, []);
+}
+
+#[test]
+fn reports_output_as_a_diff() {
+    let expectations =
+        Expectations::parse("// expect: 1\n// expect: 2\n// expect: 3", None).unwrap();
+    let failures = check(&expectations, &run("1\n2.0\n3\n", "", 0));
+    assert_eq!(
+        failures,
+        [Failure::Output(vec![
+            "  1".to_string(),
+            "- 2".to_string(),
+            "+ 2.0".to_string(),
+            "  3".to_string(),
+        ])]
+    );
+}
+
+#[test]
+fn reports_wrong_errors() {
+    let expectations = Expectations::parse("-nil; // expect runtime error: Bad.", None).unwrap();
+    let failures = check(&expectations, &run("", "Bad.\n[line 3] in script\n", 70));
+    assert_eq!(
+        failures,
+        [Failure::RuntimeErrorLine {
+            expected: 1,
+            actual: Some(3)
+        }]
+    );
+
+    let expectations =
+        Expectations::parse("var = 1; // Error at '=': Expect variable name.", None).unwrap();
+    let failures = check(
+        &expectations,
+        &run("", "[line 1] Error at '1': Oops.\n", 65),
+    );
+    assert_eq!(
+        failures,
+        [
+            Failure::MissingCompileError(
+                "[line 1] Error at '=': Expect variable name.".to_string()
+            ),
+            Failure::UnexpectedError("[line 1] Error at '1': Oops.".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn reports_wrong_exit_code() {
+    let expectations = Expectations::parse("print 1; // expect: 1", None).unwrap();
+    let failures = check(&expectations, &run("1\n", "", 1));
+    assert_eq!(
+        failures,
+        [Failure::ExitCode {
+            expected: 0,
+            actual: Some(1)
+        }]
+    );
+}
+
+#[test]
+fn diffs_lines() {
+    assert_eq!(diff(&["a", "b"], &["a", "b"]), ["  a", "  b"]);
+    assert_eq!(
+        diff(&["a", "b", "c"], &["a", "c", "d"]),
+        ["  a", "- b", "  c", "+ d"]
+    );
+    assert_eq!(diff(&[], &["x"]), ["+ x"]);
+}
@@ -0,0 +1,49 @@
+//! How Lox prints values, shared by both interpreters so their output
+//! can be checked against the same expectations.
+//!
+//! Numbers print the way the reference implementation's `printf("%g")`
+//! does: six significant digits, no trailing zeros, an exponent only
+//! for very large or very small magnitudes, and `nan`, `inf` and `-0`
+//! spelled out.
+
+use std::fmt;
+
+/// A number as Lox prints it.
+#[derive(Debug, Clone, Copy)]
+pub struct Number(pub f64);
+
+/// Significant digits, as with `%g`.
+const PRECISION: i32 = 6;
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let n = self.0;
+        if n.is_nan() {
+            return f.write_str("nan");
+        }
+        if n.is_infinite() {
+            return f.write_str(if n < 0.0 { "-inf" } else { "inf" });
+        }
+
+        // the exponent after rounding to the precision decides the style
+        let scientific = format!("{:.*e}", (PRECISION - 1) as usize, n);
+        let (mantissa, exponent) = scientific.split_once('e').expect("an exponent");
+        let exponent: i32 = exponent.parse().expect("a decimal exponent");
+        if (-4..PRECISION).contains(&exponent) {
+            let fixed = format!("{:.*}", (PRECISION - 1 - exponent) as usize, n);
+            f.write_str(trim_zeros(&fixed))
+        } else {
+            let sign = if exponent < 0 { '-' } else { '+' };
+            write!(f, "{}e{sign}{:02}", trim_zeros(mantissa), exponent.abs())
+        }
+    }
+}
+
+/// Drop the zeros ending a fraction, and its point if nothing is left.
+fn trim_zeros(digits: &str) -> &str {
+    if digits.contains('.') {
+        digits.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        digits
+    }
+}
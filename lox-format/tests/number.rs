@@ -0,0 +1,46 @@
+use lox_format::Number;
+
+fn format(n: f64) -> String {
+    Number(n).to_string()
+}
+
+#[test]
+fn integers_have_no_fraction() {
+    assert_eq!(format(0.0), "0");
+    assert_eq!(format(2.0), "2");
+    assert_eq!(format(-123.0), "-123");
+    assert_eq!(format(987654.0), "987654");
+}
+
+#[test]
+fn fractions_drop_trailing_zeros() {
+    assert_eq!(format(-6.2), "-6.2");
+    assert_eq!(format(123.456), "123.456");
+    assert_eq!(format(-0.001), "-0.001");
+    assert_eq!(format(0.5), "0.5");
+}
+
+#[test]
+fn six_significant_digits() {
+    assert_eq!(format(1.0 / 3.0), "0.333333");
+    assert_eq!(format(0.1 + 0.2), "0.3");
+    assert_eq!(format(2.0 / 3.0), "0.666667");
+    assert_eq!(format(999999.5), "1e+06");
+}
+
+#[test]
+fn exponents_for_large_and_small_magnitudes() {
+    assert_eq!(format(1234567.0), "1.23457e+06");
+    assert_eq!(format(1e100), "1e+100");
+    assert_eq!(format(0.0001), "0.0001");
+    assert_eq!(format(0.00001), "1e-05");
+    assert_eq!(format(-2.5e-10), "-2.5e-10");
+}
+
+#[test]
+fn special_values() {
+    assert_eq!(format(-0.0), "-0");
+    assert_eq!(format(f64::NAN), "nan");
+    assert_eq!(format(f64::INFINITY), "inf");
+    assert_eq!(format(f64::NEG_INFINITY), "-inf");
+}
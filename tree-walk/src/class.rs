@@ -0,0 +1,80 @@
+//! Classes and their instances, as values at runtime.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::environment::Environment;
+use crate::eval::ExprEval;
+use crate::program::Function;
+
+pub struct LoxClass {
+    pub name: String,
+    pub superclass: Option<Rc<LoxClass>>,
+    pub methods: HashMap<String, Rc<Function>>,
+    /// What the methods close over: the scope the class was declared
+    /// in, or one inside it holding `super`.
+    pub closure: Environment,
+}
+
+impl LoxClass {
+    /// The method called `name` and the environment it closes over,
+    /// looking in the superclasses if this class doesn't have it.
+    pub fn find_method(&self, name: &str) -> Option<(Rc<Function>, Environment)> {
+        match self.methods.get(name) {
+            Some(method) => Some((Rc::clone(method), self.closure.clone())),
+            None => self.superclass.as_ref()?.find_method(name),
+        }
+    }
+}
+
+// Classes are only ever equal to themselves
+impl PartialEq for LoxClass {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for LoxClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+pub struct LoxInstance {
+    pub class: Rc<LoxClass>,
+    pub fields: HashMap<String, ExprEval>,
+}
+
+impl LoxInstance {
+    pub fn new(class: Rc<LoxClass>) -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(LoxInstance {
+            class,
+            fields: HashMap::new(),
+        }))
+    }
+}
+
+// Instances are only ever equal to themselves
+impl PartialEq for LoxInstance {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+// An instance's fields can hold the instance itself, so this mustn't
+// print them
+impl fmt::Debug for LoxInstance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
+
+/// The environment a method closing over `closure` runs in when it's
+/// called on `instance`: one with `this` bound.
+pub fn bind(closure: &Environment, instance: ExprEval) -> Environment {
+    let mut environment = closure.enclosed();
+    environment.define("this".to_string(), instance);
+    environment
+}
@@ -1,73 +1,126 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
 
-use crate::error::{Result, parse_error};
 use crate::eval::ExprEval;
 
+/// The variables of one scope, and the scope it's nested in.
 pub struct Scope {
-    values: HashMap<String, Option<ExprEval>>,
+    values: HashMap<String, ExprEval>,
+    parent: Option<Rc<RefCell<Scope>>>,
 }
 
+/// A handle on the innermost scope.  Scopes are shared, so a function
+/// can hold on to the one it was declared in after the block that
+/// declared it is done with it.  Cloning the handle shares the scope.
+#[derive(Clone)]
 pub struct Environment {
-    scopes: Vec<Scope>,
+    scope: Rc<RefCell<Scope>>,
 }
 
 impl Environment {
     pub fn new() -> Self {
-        let mut env = Environment { scopes: Vec::new() };
-        env.enter_scope();
-        env
+        Environment {
+            scope: Rc::new(RefCell::new(Scope {
+                values: HashMap::new(),
+                parent: None,
+            })),
+        }
+    }
+
+    /// A new, empty scope nested in this one.
+    pub fn enclosed(&self) -> Self {
+        Environment {
+            scope: Rc::new(RefCell::new(Scope {
+                values: HashMap::new(),
+                parent: Some(Rc::clone(&self.scope)),
+            })),
+        }
     }
 
     pub fn enter_scope(&mut self) {
-        self.scopes.push(Scope {
-            values: HashMap::new(),
-        });
+        *self = self.enclosed();
     }
 
     pub fn exit_scope(&mut self) {
-        self.scopes.pop();
+        let parent = self.scope.borrow().parent.clone();
+        if let Some(parent) = parent {
+            self.scope = parent;
+        }
     }
 
-    pub fn define(&mut self, identifier: String, value: Option<ExprEval>) {
-        // self.values.insert(identifier, value);
-        self.scopes
-            .last_mut()
-            .unwrap()
-            .values
-            .insert(identifier, value);
+    pub fn define(&mut self, identifier: String, value: ExprEval) {
+        self.scope.borrow_mut().values.insert(identifier, value);
     }
 
-    pub fn assign(&mut self, identifier: String, value: Option<ExprEval>) -> bool {
-        for scope in self.scopes.iter_mut().rev() {
-            if scope.values.contains_key(&identifier) {
-                scope.values.insert(identifier, value);
-                return true;
+    /// The scope `depth` scopes out from this one, or the global
+    /// scope if `depth` is `None`.
+    fn ancestor(&self, depth: Option<usize>) -> Rc<RefCell<Scope>> {
+        let mut scope = Rc::clone(&self.scope);
+        let mut hops = 0;
+        loop {
+            if depth == Some(hops) {
+                return scope;
             }
+            let parent = scope.borrow().parent.clone();
+            match parent {
+                Some(parent) => scope = parent,
+                // only the global scope has no parent
+                None => return scope,
+            }
+            hops += 1;
         }
-        false
     }
 
-    pub fn get(&self, identifier: &str) -> Result<&Option<ExprEval>> {
-        for scope in self.scopes.iter().rev() {
-            if let Some(val) = scope.values.get(identifier) {
-                return Ok(val);
+    /// Set a variable `depth` scopes out, or say it doesn't exist.
+    pub fn assign_at(&mut self, depth: Option<usize>, identifier: &str, value: ExprEval) -> bool {
+        let scope = self.ancestor(depth);
+        match scope.borrow_mut().values.get_mut(identifier) {
+            Some(slot) => {
+                *slot = value;
+                true
             }
+            None => false,
         }
-        Err(parse_error::<Option<ExprEval>>(&format!(
-            "Undefined variable access {:?}",
-            identifier,
-        )))
     }
 
+    /// A variable `depth` scopes out, or `None` if it doesn't exist.
+    pub fn get_at(&self, depth: Option<usize>, identifier: &str) -> Option<ExprEval> {
+        self.ancestor(depth)
+            .borrow()
+            .values
+            .get(identifier)
+            .cloned()
+    }
+
+    /// Print every scope, innermost first, to stderr.
     pub fn debug_dump(&self) {
-        for scope in self.scopes.iter().rev() {
-            println!("scope");
-            println!("-----");
-            for (key, value) in &scope.values {
-                println!("({:?} -> {:?})", key, value)
+        let mut scope = Some(Rc::clone(&self.scope));
+        while let Some(current) = scope {
+            eprintln!("scope");
+            eprintln!("-----");
+            for (key, value) in &current.borrow().values {
+                eprintln!("({:?} -> {:?})", key, value)
             }
-            println!("-----");
+            eprintln!("-----");
+            scope = current.borrow().parent.clone();
         }
-        println!("\n");
+        eprintln!("\n");
+    }
+}
+
+// Two handles are the same environment if they share a scope
+impl PartialEq for Environment {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.scope, &other.scope)
+    }
+}
+
+// A function's environment can hold the function itself, so this
+// mustn't print what's in it
+impl fmt::Debug for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Environment")
     }
 }
@@ -5,15 +5,15 @@ pub type Result<T> = std::result::Result<T, Box<dyn error::Error>>;
 
 #[derive(Debug)]
 pub enum Error {
-    RuntimeError(String),
+    RuntimeError(RuntimeError),
     ParseError(String),
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Error::RuntimeError(msg) => write!(f, "Runtime error! {:?}", msg),
-            Error::ParseError(msg) => write!(f, "Parse error! {:?}", msg),
+            Error::RuntimeError(error) => write!(f, "{}", error),
+            Error::ParseError(msg) => write!(f, "{}", msg),
         }
     }
 }
@@ -28,6 +28,31 @@ pub fn eof_parse_error<T>() -> Box<Error> {
     parse_error::<T>("Reached end of input while parsing")
 }
 
-pub fn runtime_error<T>(msg: &str) -> Box<Error> {
-    Box::new(Error::RuntimeError(msg.to_string()))
+/// An error that stops a running program, and the line it happened on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub message: String,
+    pub line: usize,
+}
+
+impl RuntimeError {
+    pub fn new(message: impl Into<String>, line: usize) -> Self {
+        RuntimeError {
+            message: message.into(),
+            line,
+        }
+    }
+}
+
+// Laid out the way the book's interpreter reports them
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\n[line {}]", self.message, self.line)
+    }
+}
+
+impl From<RuntimeError> for Box<dyn error::Error> {
+    fn from(error: RuntimeError) -> Self {
+        Box::new(Error::RuntimeError(error))
+    }
 }
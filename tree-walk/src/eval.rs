@@ -1,11 +1,20 @@
 use crate::{
+    class::{LoxClass, LoxInstance, bind},
     environment::Environment,
-    lexer::TokenType,
-    program::{BinaryOp, Declaration, Expr, Literal, Program, Statement, UnaryOp},
+    error::RuntimeError,
+    lexer::{Token, TokenType},
+    program::{
+        BinaryOp, Class, Declaration, Expr, Function, Literal, LogicalOp, Program, Statement,
+        UnaryOp,
+    },
 };
+use lox_format::Number;
 use std::{
-    cmp::Ordering,
+    cell::RefCell,
+    fmt,
+    io::Write,
     ops::{Add, Div, Mul, Sub},
+    rc::Rc,
 };
 
 #[derive(Clone, Debug, PartialEq)]
@@ -14,286 +23,596 @@ pub enum ExprEval {
     String(String),
     Bool(bool),
     Nil,
+    // A function and the environment it closes over
+    Function(Rc<Function>, Environment),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
 
-    RuntimeError(String),
-    RuntimeTypeError(String),
+    // Not a value: a `return` statement unwinding to its call
+    Return(Box<ExprEval>),
+}
+
+impl fmt::Display for ExprEval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExprEval::Number(n) => write!(f, "{}", Number(*n)),
+            ExprEval::String(s) => write!(f, "{}", s),
+            ExprEval::Bool(b) => write!(f, "{}", b),
+            ExprEval::Nil => write!(f, "nil"),
+            ExprEval::Function(function, _) => write!(f, "<fn {}>", function.name),
+            ExprEval::Class(class) => write!(f, "{}", class.name),
+            ExprEval::Instance(instance) => write!(f, "{} instance", instance.borrow().class.name),
+            ExprEval::Return(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// What counts as true in a condition, the operand of `!`, or an
+/// operand of `and` and `or`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Truthiness {
+    /// Only bools: anything else is a runtime error.
+    #[default]
+    Strict,
+    /// As in the book: `nil` and `false` are false, anything else is
+    /// true, and `and` and `or` give back whichever operand decided
+    /// them.
+    Reference,
+}
+
+/// Everything a running program needs besides its AST.
+pub struct Interpreter<'out> {
+    pub environment: Environment,
+    /// Where `print` writes.
+    pub output: Box<dyn Write + 'out>,
+    pub truthiness: Truthiness,
+    /// Whether to dump the environment to stderr on every variable
+    /// access.
+    pub trace_env: bool,
+    /// Calls in progress, so runaway recursion is a runtime error
+    /// rather than an overflow of the Rust stack.
+    depth: usize,
+}
+
+impl<'out> Interpreter<'out> {
+    pub fn new(output: Box<dyn Write + 'out>, truthiness: Truthiness, trace_env: bool) -> Self {
+        Interpreter {
+            environment: Environment::new(),
+            output,
+            truthiness,
+            trace_env,
+            depth: 0,
+        }
+    }
+
+    /// Whether `value` counts as true, or `None` if it can't be used
+    /// as a condition.
+    fn truth(&self, value: &ExprEval) -> Option<bool> {
+        match (self.truthiness, value) {
+            (Truthiness::Strict, ExprEval::Bool(b)) => Some(*b),
+            (Truthiness::Strict, _) => None,
+            (Truthiness::Reference, value) => Some(is_truthy(value)),
+        }
+    }
 }
 
 pub trait Eval {
-    fn eval(&self, environment: &mut Environment) -> ExprEval;
+    fn eval(&self, interpreter: &mut Interpreter) -> Result<ExprEval, RuntimeError>;
 }
 
 impl Eval for Program {
-    fn eval(&self, environment: &mut Environment) -> ExprEval {
+    fn eval(&self, interpreter: &mut Interpreter) -> Result<ExprEval, RuntimeError> {
         for decl in self.into_iter() {
-            decl.eval(environment);
+            decl.eval(interpreter)?;
         }
-        ExprEval::Nil
+        Ok(ExprEval::Nil)
     }
 }
 
 impl Eval for Declaration {
-    fn eval(&self, environment: &mut Environment) -> ExprEval {
+    fn eval(&self, interpreter: &mut Interpreter) -> Result<ExprEval, RuntimeError> {
         match self {
-            Declaration::Variable { identifier, value } => {
-                if let Some(expr) = value {
-                    let rhs = expr.eval(environment);
-                    environment.define(identifier.clone(), Some(rhs));
-                } else {
-                    environment.define(identifier.clone(), None);
-                }
-                ExprEval::Nil
+            Declaration::Variable {
+                identifier, value, ..
+            } => {
+                // without an initializer it's nil, even if it's
+                // redeclaring a global that had a value
+                let rhs = match value {
+                    Some(expr) => expr.eval(interpreter)?,
+                    None => ExprEval::Nil,
+                };
+                interpreter.environment.define(identifier.clone(), rhs);
+                Ok(ExprEval::Nil)
             }
-            Declaration::Statement(statement) => statement.eval(environment),
+            Declaration::Function(function) => {
+                let value =
+                    ExprEval::Function(Rc::clone(function), interpreter.environment.clone());
+                interpreter.environment.define(function.name.clone(), value);
+                Ok(ExprEval::Nil)
+            }
+            Declaration::Class(class) => declare_class(class, interpreter),
+            Declaration::Statement(statement) => statement.eval(interpreter),
         }
     }
 }
 
+fn declare_class(class: &Class, interpreter: &mut Interpreter) -> Result<ExprEval, RuntimeError> {
+    let superclass = match &class.superclass {
+        Some(expr) => match expr.eval(interpreter)? {
+            ExprEval::Class(superclass) => Some(superclass),
+            _ => return Err(RuntimeError::new("Superclass must be a class.", class.line)),
+        },
+        None => None,
+    };
+    let mut closure = interpreter.environment.clone();
+    if let Some(superclass) = &superclass {
+        closure = closure.enclosed();
+        closure.define("super".to_string(), ExprEval::Class(Rc::clone(superclass)));
+    }
+    let methods = class
+        .methods
+        .iter()
+        .map(|method| (method.name.clone(), Rc::clone(method)))
+        .collect();
+    let value = ExprEval::Class(Rc::new(LoxClass {
+        name: class.name.clone(),
+        superclass,
+        methods,
+        closure,
+    }));
+    interpreter.environment.define(class.name.clone(), value);
+    Ok(ExprEval::Nil)
+}
+
 impl Eval for Statement {
-    fn eval(&self, environment: &mut Environment) -> ExprEval {
+    fn eval(&self, interpreter: &mut Interpreter) -> Result<ExprEval, RuntimeError> {
         match self {
-            Statement::Expr(expr) => {
-                let res = expr.eval(environment);
-                println!("Evaluated to {:?}", res);
-                res
-            }
-            Statement::Print(expr) => {
-                let res = expr.eval(environment);
-                println!("Print called on: {:?}", res);
-                res
+            Statement::Expr(expr) => expr.eval(interpreter),
+            Statement::Print(expr, line) => {
+                let res = expr.eval(interpreter)?;
+                writeln!(interpreter.output, "{}", res).map_err(|e| {
+                    RuntimeError::new(format!("Couldn't write output: {}", e), *line)
+                })?;
+                Ok(res)
             }
             Statement::Block(statements) => {
-                environment.enter_scope();
-                for stmt in statements {
-                    stmt.eval(environment);
-                }
-                environment.exit_scope();
-                ExprEval::Nil
+                interpreter.environment.enter_scope();
+                let res = eval_block(statements, interpreter);
+                interpreter.environment.exit_scope();
+                res
             }
-            Statement::IfElse(cond, then_branch, else_branch) => match cond.eval(environment) {
-                ExprEval::Bool(b) => {
-                    if b {
-                        return then_branch.eval(environment);
-                    } else {
-                        if let Some(else_branch) = &**else_branch {
-                            return else_branch.eval(environment);
+            Statement::IfElse(cond, then_branch, else_branch, line) => {
+                let cond = cond.eval(interpreter)?;
+                match interpreter.truth(&cond) {
+                    Some(b) => {
+                        if b {
+                            then_branch.eval(interpreter)
+                        } else if let Some(else_branch) = &**else_branch {
+                            else_branch.eval(interpreter)
+                        } else {
+                            Ok(ExprEval::Nil)
                         }
-                        return ExprEval::Nil;
                     }
+                    None => Err(not_a_bool(*line)),
                 }
-                _ => ExprEval::RuntimeTypeError(
-                    "Expression in conditional didn't evaluate to a bool".to_string(),
-                ),
-            },
-            Statement::While(cond, body) => loop {
-                match cond.eval(environment) {
-                    ExprEval::Bool(cond) => {
+            }
+            Statement::While(cond, body, line) => loop {
+                let cond = cond.eval(interpreter)?;
+                match interpreter.truth(&cond) {
+                    Some(cond) => {
                         if cond {
-                            body.eval(environment);
+                            if let res @ ExprEval::Return(_) = body.eval(interpreter)? {
+                                return Ok(res);
+                            }
                         } else {
-                            return ExprEval::Nil;
+                            return Ok(ExprEval::Nil);
                         }
                     }
-                    _ => {
-                        return ExprEval::RuntimeTypeError(
-                            "Trying to evaluate non-bool type as a loop condition".to_string(),
-                        );
-                    }
+                    None => return Err(not_a_bool(*line)),
                 }
             },
-            Statement::For(initializer, condition, increment, body) => {
+            Statement::For(initializer, condition, increment, body, line) => {
                 // Create a new scope for the for loop
-                environment.enter_scope();
-                
-                // Run the initializer if it exists
-                if let Some(init) = initializer {
-                    init.eval(environment);
-                }
-                
-                // If no condition is provided, use 'true'
-                let result = match condition {
-                    Some(cond) => {
-                        // Loop until the condition is false
-                        loop {
-                            match cond.eval(environment) {
-                                ExprEval::Bool(b) => {
-                                    if !b {
-                                        break ExprEval::Nil;
-                                    }
-                                    
-                                    // Execute the body
-                                    body.eval(environment);
-                                    
-                                    // Execute the increment
-                                    if let Some(inc) = increment {
-                                        inc.eval(environment);
-                                    }
-                                },
-                                _ => {
-                                    break ExprEval::RuntimeTypeError(
-                                        "For loop condition did not evaluate to a boolean".to_string(),
-                                    );
-                                }
-                            }
-                        }
-                    },
-                    None => {
-                        // Infinite loop with no condition (equivalent to 'while true')
-                        loop {
-                            body.eval(environment);
-                            
-                            // Execute the increment
-                            if let Some(inc) = increment {
-                                inc.eval(environment);
-                            }
-                        }
-                    }
-                };
-                
-                // Exit the scope after the loop is done
-                environment.exit_scope();
+                interpreter.environment.enter_scope();
+                let result = eval_for(
+                    initializer.as_deref(),
+                    condition.as_deref(),
+                    increment.as_deref(),
+                    body,
+                    *line,
+                    interpreter,
+                );
+                // Exit the scope after the loop is done, even if it failed
+                interpreter.environment.exit_scope();
                 result
-            },
+            }
+            Statement::Return(_, value) => {
+                let value = match value {
+                    Some(expr) => expr.eval(interpreter)?,
+                    None => ExprEval::Nil,
+                };
+                Ok(ExprEval::Return(Box::new(value)))
+            }
+        }
+    }
+}
+
+/// Run a for loop inside the scope made for it.
+fn eval_for(
+    initializer: Option<&Declaration>,
+    condition: Option<&Expr>,
+    increment: Option<&Expr>,
+    body: &Statement,
+    line: usize,
+    interpreter: &mut Interpreter,
+) -> Result<ExprEval, RuntimeError> {
+    // Run the initializer if it exists
+    if let Some(init) = initializer {
+        init.eval(interpreter)?;
+    }
+
+    loop {
+        // If no condition is provided, use 'true'
+        if let Some(cond) = condition {
+            let cond = cond.eval(interpreter)?;
+            match interpreter.truth(&cond) {
+                Some(true) => {}
+                Some(false) => return Ok(ExprEval::Nil),
+                None => return Err(not_a_bool(line)),
+            }
+        }
+
+        // Execute the body
+        if let res @ ExprEval::Return(_) = body.eval(interpreter)? {
+            return Ok(res);
+        }
+
+        // Execute the increment
+        if let Some(inc) = increment {
+            inc.eval(interpreter)?;
         }
     }
 }
 
-// The book allows things like truthiness for other types, but I can't abide that
+// The book allows things like truthiness for other types, but I can't
+// abide that unless asked to: see `Truthiness`
 impl Eval for Expr {
-    fn eval(&self, environment: &mut Environment) -> ExprEval {
+    fn eval(&self, interpreter: &mut Interpreter) -> Result<ExprEval, RuntimeError> {
         match self {
-            Expr::Assignment(lhs, rhs) => match (&lhs.token_type, rhs.eval(environment)) {
-                (TokenType::Identifier { name }, rhs) => {
-                    if environment.get(name).is_ok() {
-                        environment.assign(name.clone(), Some(rhs.clone()));
-                        rhs
-                    } else {
-                        ExprEval::RuntimeError(format!("Assigned to unknown variable {:?}", name))
+            Expr::Assignment(lhs, rhs, depth) => {
+                let rhs = rhs.eval(interpreter)?;
+                match &lhs.token_type {
+                    TokenType::Identifier { name } => {
+                        if interpreter
+                            .environment
+                            .assign_at(depth.get(), name, rhs.clone())
+                        {
+                            Ok(rhs)
+                        } else {
+                            Err(RuntimeError::new(
+                                format!("Undefined variable '{}'.", name),
+                                lhs.line,
+                            ))
+                        }
                     }
+                    _ => Err(RuntimeError::new("Illegal assignment", lhs.line)),
                 }
-                _ => ExprEval::RuntimeError("Illegal assignment".to_string()),
-            },
-            Expr::Binary(lhs, binary_op, rhs) => {
-                match (lhs.eval(environment), binary_op, rhs.eval(environment)) {
-                    (lhs, BinaryOp::Equal, rhs) => ExprEval::Bool(lhs == rhs),
-                    (lhs, BinaryOp::NotEqual, rhs) => ExprEval::Bool(lhs != rhs),
-                    (lhs, BinaryOp::Less, rhs) => ExprEval::Bool(lhs < rhs),
-                    (lhs, BinaryOp::LessEqual, rhs) => ExprEval::Bool(lhs <= rhs),
-                    (lhs, BinaryOp::Greater, rhs) => ExprEval::Bool(lhs > rhs),
-                    (lhs, BinaryOp::GreaterEqual, rhs) => ExprEval::Bool(lhs >= rhs),
-                    (lhs, BinaryOp::Plus, rhs) => lhs + rhs,
-                    (lhs, BinaryOp::Minus, rhs) => lhs - rhs,
-                    (lhs, BinaryOp::Times, rhs) => lhs * rhs,
-                    (lhs, BinaryOp::Div, rhs) => lhs / rhs,
+            }
+            Expr::Binary(lhs, binary_op, rhs, line) => {
+                let lhs = lhs.eval(interpreter)?;
+                let rhs = rhs.eval(interpreter)?;
+                let res = match binary_op {
+                    BinaryOp::Equal => Ok(ExprEval::Bool(lhs == rhs)),
+                    BinaryOp::NotEqual => Ok(ExprEval::Bool(lhs != rhs)),
+                    BinaryOp::Less => compare(lhs, rhs, |l, r| l < r),
+                    BinaryOp::LessEqual => compare(lhs, rhs, |l, r| l <= r),
+                    BinaryOp::Greater => compare(lhs, rhs, |l, r| l > r),
+                    BinaryOp::GreaterEqual => compare(lhs, rhs, |l, r| l >= r),
+                    BinaryOp::Plus => lhs + rhs,
+                    BinaryOp::Minus => lhs - rhs,
+                    BinaryOp::Times => lhs * rhs,
+                    BinaryOp::Div => lhs / rhs,
+                };
+                res.map_err(|msg| RuntimeError::new(msg, *line))
+            }
+            Expr::Unary(unary_op, expr, line) => {
+                let e = expr.eval(interpreter)?;
+                match (unary_op, &e) {
+                    (UnaryOp::Negate, ExprEval::Number(n)) => Ok(ExprEval::Number(-n)),
+                    (UnaryOp::Negate, _) => {
+                        Err(RuntimeError::new("Operand must be a number.", *line))
+                    }
+                    (UnaryOp::Not, e) => match interpreter.truth(e) {
+                        Some(b) => Ok(ExprEval::Bool(!b)),
+                        None => Err(not_a_bool(*line)),
+                    },
+                }
+            }
+            Expr::Call(callee, args, line) => {
+                let callee = callee.eval(interpreter)?;
+                let args = args
+                    .iter()
+                    .map(|arg| arg.eval(interpreter))
+                    .collect::<Result<Vec<_>, _>>()?;
+                match callee {
+                    ExprEval::Function(function, closure) => {
+                        call(&function, &closure, args, *line, interpreter)
+                    }
+                    ExprEval::Class(class) => instantiate(&class, args, *line, interpreter),
+                    _ => Err(RuntimeError::new(
+                        "Can only call functions and classes.",
+                        *line,
+                    )),
                 }
             }
-            Expr::Unary(unary_op, expr) => match (unary_op, expr.eval(environment)) {
-                (UnaryOp::Negate, ExprEval::Number(n)) => ExprEval::Number(-n),
-                (UnaryOp::Not, ExprEval::Bool(b)) => ExprEval::Bool(!b),
-                (_, e @ ExprEval::RuntimeTypeError(_)) => e,
-                (_, e) => {
-                    ExprEval::RuntimeTypeError(format!("Can't apply {:?} to {:?}", unary_op, e))
+            Expr::Get(object, name) => match object.eval(interpreter)? {
+                ExprEval::Instance(instance) => get_property(&instance, name),
+                _ => Err(RuntimeError::new(
+                    "Only instances have properties.",
+                    name.line,
+                )),
+            },
+            Expr::Set(object, name, value) => match object.eval(interpreter)? {
+                ExprEval::Instance(instance) => {
+                    let value = value.eval(interpreter)?;
+                    instance
+                        .borrow_mut()
+                        .fields
+                        .insert(name.lexeme.clone(), value.clone());
+                    Ok(value)
                 }
+                _ => Err(RuntimeError::new("Only instances have fields.", name.line)),
             },
-            Expr::Literal(literal) => literal.eval(environment),
-            Expr::Grouping(expr) => expr.eval(environment),
-            Expr::Logical(lhs, logical_op, rhs) => {
-                // as with many things, this diverges from the book
-                // because I don't really want to implement implicit
-                // "thruthiness"
-                match (lhs.eval(environment), logical_op, rhs.eval(environment)) {
-                    (ExprEval::Bool(lhs), crate::program::LogicalOp::Or, ExprEval::Bool(rhs)) => {
-                        return ExprEval::Bool(lhs || rhs);
+            Expr::This(keyword, depth) => {
+                match interpreter.environment.get_at(depth.get(), &keyword.lexeme) {
+                    Some(this) => Ok(this),
+                    None => Err(RuntimeError::new(
+                        "Can't use 'this' outside of a class.",
+                        keyword.line,
+                    )),
+                }
+            }
+            Expr::Super(keyword, method, depth) => {
+                // `this` is bound in the scope just inside the one with `super`
+                let this_depth = depth.get().map(|depth| depth.saturating_sub(1));
+                match (
+                    interpreter.environment.get_at(depth.get(), "super"),
+                    interpreter.environment.get_at(this_depth, "this"),
+                ) {
+                    (Some(ExprEval::Class(superclass)), Some(this)) => {
+                        match superclass.find_method(&method.lexeme) {
+                            Some((method, closure)) => {
+                                Ok(ExprEval::Function(method, bind(&closure, this)))
+                            }
+                            None => Err(RuntimeError::new(
+                                format!("Undefined property '{}'.", method.lexeme),
+                                method.line,
+                            )),
+                        }
                     }
-                    (ExprEval::Bool(lhs), crate::program::LogicalOp::And, ExprEval::Bool(rhs)) => {
-                        return ExprEval::Bool(lhs && rhs);
+                    _ => Err(RuntimeError::new(
+                        "Can't use 'super' outside of a subclass.",
+                        keyword.line,
+                    )),
+                }
+            }
+            Expr::Literal(literal) => literal.eval(interpreter),
+            Expr::Grouping(expr) => expr.eval(interpreter),
+            Expr::Logical(lhs, logical_op, rhs, line) => {
+                // in strict mode the operands are bools, so giving back
+                // the one that decided it gives back a bool
+                let lhs = lhs.eval(interpreter)?;
+                let Some(decided) = interpreter.truth(&lhs) else {
+                    return Err(not_a_bool(*line));
+                };
+                match (decided, logical_op) {
+                    (true, LogicalOp::Or) | (false, LogicalOp::And) => Ok(lhs),
+                    _ => {
+                        let rhs = rhs.eval(interpreter)?;
+                        match interpreter.truth(&rhs) {
+                            Some(_) => Ok(rhs),
+                            None => Err(not_a_bool(*line)),
+                        }
                     }
-                    _ => ExprEval::RuntimeTypeError(
-                        "Logical operator applied to non-boolean value".to_string(),
-                    ),
                 }
             }
         }
     }
 }
 
+/// Whether the book would treat `value` as true.
+fn is_truthy(value: &ExprEval) -> bool {
+    !matches!(value, ExprEval::Nil | ExprEval::Bool(false))
+}
+
+/// The error for a non-bool where strict mode wants a truth value: in
+/// a condition, or as the operand of `!`, `and` or `or`.
+fn not_a_bool(line: usize) -> RuntimeError {
+    RuntimeError::new("Condition must be a boolean.", line)
+}
+
+/// Evaluate the declarations of a block in order, stopping early if
+/// one of them returns or fails.
+fn eval_block(
+    decls: &[Declaration],
+    interpreter: &mut Interpreter,
+) -> Result<ExprEval, RuntimeError> {
+    for decl in decls {
+        if let res @ ExprEval::Return(_) = decl.eval(interpreter)? {
+            return Ok(res);
+        }
+    }
+    Ok(ExprEval::Nil)
+}
+
+/// How deeply calls may nest.  Each Lox call takes several Rust
+/// frames, so this keeps well inside the interpreter thread's stack.
+const MAX_CALL_DEPTH: usize = 255;
+
+/// Call `function` from `line`.
+fn call(
+    function: &Function,
+    closure: &Environment,
+    args: Vec<ExprEval>,
+    line: usize,
+    interpreter: &mut Interpreter,
+) -> Result<ExprEval, RuntimeError> {
+    if args.len() != function.params.len() {
+        return Err(RuntimeError::new(
+            format!(
+                "Expected {} arguments but got {}.",
+                function.params.len(),
+                args.len()
+            ),
+            line,
+        ));
+    }
+    if interpreter.depth == MAX_CALL_DEPTH {
+        return Err(RuntimeError::new("Stack overflow.", line));
+    }
+    // the body runs in a new scope in the one the function was declared in
+    let caller = std::mem::replace(&mut interpreter.environment, closure.enclosed());
+    for (param, arg) in function.params.iter().zip(args) {
+        interpreter.environment.define(param.clone(), arg);
+    }
+    interpreter.depth += 1;
+    let res = eval_block(&function.body, interpreter);
+    interpreter.depth -= 1;
+    interpreter.environment = caller;
+    let res = res?;
+    if function.initializer {
+        // `init` hands back the instance, however it returns
+        if let Some(this) = closure.get_at(Some(0), "this") {
+            return Ok(this);
+        }
+    }
+    match res {
+        ExprEval::Return(value) => Ok(*value),
+        _ => Ok(ExprEval::Nil),
+    }
+}
+
+/// A new instance of `class`, set up by its `init` method if it has
+/// one.
+fn instantiate(
+    class: &Rc<LoxClass>,
+    args: Vec<ExprEval>,
+    line: usize,
+    interpreter: &mut Interpreter,
+) -> Result<ExprEval, RuntimeError> {
+    let instance = ExprEval::Instance(LoxInstance::new(Rc::clone(class)));
+    match class.find_method("init") {
+        Some((init, closure)) => call(&init, &bind(&closure, instance), args, line, interpreter),
+        None if !args.is_empty() => Err(RuntimeError::new(
+            format!("Expected 0 arguments but got {}.", args.len()),
+            line,
+        )),
+        None => Ok(instance),
+    }
+}
+
+/// A field of `instance`, or else one of its class's methods bound to
+/// it.
+fn get_property(
+    instance: &Rc<RefCell<LoxInstance>>,
+    name: &Token,
+) -> Result<ExprEval, RuntimeError> {
+    if let Some(value) = instance.borrow().fields.get(&name.lexeme) {
+        return Ok(value.clone());
+    }
+    let method = instance.borrow().class.find_method(&name.lexeme);
+    match method {
+        Some((method, closure)) => {
+            let this = ExprEval::Instance(Rc::clone(instance));
+            Ok(ExprEval::Function(method, bind(&closure, this)))
+        }
+        None => Err(RuntimeError::new(
+            format!("Undefined property '{}'.", name.lexeme),
+            name.line,
+        )),
+    }
+}
+
 impl Eval for Literal {
-    fn eval(&self, environment: &mut Environment) -> ExprEval {
+    fn eval(&self, interpreter: &mut Interpreter) -> Result<ExprEval, RuntimeError> {
         match self {
-            Literal::Number(num) => ExprEval::Number(*num),
-            Literal::String(str) => ExprEval::String(str.clone()),
-            Literal::True => ExprEval::Bool(true),
-            Literal::False => ExprEval::Bool(false),
-            Literal::Nil => ExprEval::Nil,
-            Literal::Identifier(id) => {
-                println!("variable access {:?}", id);
-                environment.debug_dump();
-
-                match environment.get(id) {
-                    Ok(Some(res)) => res.clone(),
-                    Ok(None) => ExprEval::RuntimeError(format!(
-                        "Variable {:?} accessed before definition",
-                        id
-                    )),
+            Literal::Number(num) => Ok(ExprEval::Number(*num)),
+            Literal::String(str) => Ok(ExprEval::String(str.clone())),
+            Literal::True => Ok(ExprEval::Bool(true)),
+            Literal::False => Ok(ExprEval::Bool(false)),
+            Literal::Nil => Ok(ExprEval::Nil),
+            Literal::Identifier(token, depth) => {
+                let id = &token.lexeme;
+                if interpreter.trace_env {
+                    eprintln!("variable access {:?}", id);
+                    interpreter.environment.debug_dump();
+                }
 
-                    Err(_) => ExprEval::RuntimeError(format!("Unknown variable {:?}", id)),
+                match interpreter.environment.get_at(depth.get(), id) {
+                    Some(res) => Ok(res),
+                    None => Err(RuntimeError::new(
+                        format!("Undefined variable '{}'.", id),
+                        token.line,
+                    )),
                 }
             }
         }
     }
 }
 
-impl PartialOrd for ExprEval {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        match (self, other) {
-            (ExprEval::Number(l), ExprEval::Number(r)) => Some(l.total_cmp(r)),
-            (ExprEval::String(l), ExprEval::String(r)) => Some(l.cmp(r)),
-            _ => None,
-        }
+// The arithmetic operators fail with just a message; the expression
+// they're in knows the line
+
+/// Compare two numbers with `op`, which sees them as plain `f64`s so
+/// that any comparison with NaN is false.
+fn compare(lhs: ExprEval, rhs: ExprEval, op: fn(f64, f64) -> bool) -> Result<ExprEval, String> {
+    match (lhs, rhs) {
+        (ExprEval::Number(l), ExprEval::Number(r)) => Ok(ExprEval::Bool(op(l, r))),
+        _ => Err("Operands must be numbers.".to_string()),
     }
 }
 
 impl Add for ExprEval {
-    type Output = ExprEval;
+    type Output = Result<ExprEval, String>;
 
     fn add(self, rhs: Self) -> Self::Output {
         match (self, rhs) {
-            (ExprEval::Number(l), ExprEval::Number(r)) => ExprEval::Number(l + r),
-            (ExprEval::String(l), ExprEval::String(r)) => ExprEval::String(format!("{l}{r}")),
-            (l, r) => ExprEval::RuntimeTypeError(format!("Can't add {:?} and {:?}", l, r)),
+            (ExprEval::Number(l), ExprEval::Number(r)) => Ok(ExprEval::Number(l + r)),
+            (ExprEval::String(l), ExprEval::String(r)) => Ok(ExprEval::String(format!("{l}{r}"))),
+            _ => Err("Operands must be two numbers or two strings.".to_string()),
         }
     }
 }
 
 impl Sub for ExprEval {
-    type Output = ExprEval;
+    type Output = Result<ExprEval, String>;
 
     fn sub(self, rhs: Self) -> Self::Output {
         match (self, rhs) {
-            (ExprEval::Number(l), ExprEval::Number(r)) => ExprEval::Number(l - r),
-            (l, r) => ExprEval::RuntimeTypeError(format!("Can't subtract {:?} from {:?}", r, l)),
+            (ExprEval::Number(l), ExprEval::Number(r)) => Ok(ExprEval::Number(l - r)),
+            _ => Err("Operands must be numbers.".to_string()),
         }
     }
 }
 
 impl Mul for ExprEval {
-    type Output = ExprEval;
+    type Output = Result<ExprEval, String>;
 
     fn mul(self, rhs: Self) -> Self::Output {
         match (self, rhs) {
-            (ExprEval::Number(l), ExprEval::Number(r)) => ExprEval::Number(l * r),
-            (l, r) => ExprEval::RuntimeTypeError(format!("Can't multiply {:?} and {:?}", l, r)),
+            (ExprEval::Number(l), ExprEval::Number(r)) => Ok(ExprEval::Number(l * r)),
+            _ => Err("Operands must be numbers.".to_string()),
         }
     }
 }
 
 impl Div for ExprEval {
-    type Output = ExprEval;
+    type Output = Result<ExprEval, String>;
 
     fn div(self, rhs: Self) -> Self::Output {
         match (self, rhs) {
-            (ExprEval::Number(l), ExprEval::Number(r)) => ExprEval::Number(l / r),
-            (l, r) => ExprEval::RuntimeTypeError(format!("Can't divide {:?} by {:?}", l, r)),
+            (ExprEval::Number(l), ExprEval::Number(r)) => Ok(ExprEval::Number(l / r)),
+            _ => Err("Operands must be numbers.".to_string()),
         }
     }
 }
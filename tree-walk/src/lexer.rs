@@ -1,4 +1,3 @@
-use core::panic;
 use lazy_static::lazy_static;
 use std::collections::HashMap;
 use std::{char, iter::Peekable, str::Chars};
@@ -95,7 +94,9 @@ impl Lexer {
         }
     }
 
-    pub fn tokens(mut self) -> Vec<Token> {
+    /// The tokens of the source, ending with `Eof`, and an error for
+    /// each stretch of it that isn't a token.
+    pub fn tokens(mut self) -> (Vec<Token>, Vec<String>) {
         let mut chars = self.scanner.scan().peekable();
         let mut tokens = Vec::new();
         let mut line = 1;
@@ -124,10 +125,11 @@ impl Lexer {
                 }
                 '\n' => line += 1,
 
-                '"' => {
-                    line = string_lookahead(&mut chars, &mut tokens, line);
-                }
-                char if char.is_numeric() => {
+                '"' => match string_lookahead(&mut chars, &mut tokens, line) {
+                    Some(end) => line = end,
+                    None => self.errors.push(error(line, "Unterminated string.")),
+                },
+                char if char.is_ascii_digit() => {
                     number_lookahead(char, &mut chars, &mut tokens, line);
                 }
                 char if char.is_alphabetic() => {
@@ -135,7 +137,7 @@ impl Lexer {
                 }
                 char if char.is_ascii_whitespace() => {}
 
-                _ => self.errors.push("error".to_string()),
+                _ => self.errors.push(error(line, "Unexpected character.")),
             };
         }
         tokens.push(Token {
@@ -143,10 +145,14 @@ impl Lexer {
             line,
             lexeme: String::new(),
         });
-        return tokens;
+        return (tokens, self.errors);
     }
 }
 
+fn error(line: usize, msg: &str) -> String {
+    format!("[line {}] Error: {}", line, msg)
+}
+
 fn identifier_lookahead(
     char: char,
     chars: &mut Peekable<Chars<'_>>,
@@ -191,9 +197,15 @@ fn number_lookahead(
     line: usize,
 ) {
     let mut lexeme = char.to_string();
-    while let Some(digit) = chars.peek() {
-        if *digit == '.' || digit.is_numeric() {
-            lexeme.push(*digit);
+    let mut fraction = false;
+    while let Some(&digit) = chars.peek() {
+        // a '.' is only part of the number if a digit follows it, and
+        // only the first one
+        let point =
+            digit == '.' && !fraction && chars.clone().nth(1).is_some_and(|c| c.is_ascii_digit());
+        if point || digit.is_ascii_digit() {
+            fraction |= point;
+            lexeme.push(digit);
             chars.next();
         } else {
             break;
@@ -207,11 +219,13 @@ fn number_lookahead(
     })
 }
 
+/// Lex a string after its opening quote, and return the line it ends
+/// on, or `None` if it doesn't.
 fn string_lookahead(
     chars: &mut Peekable<Chars<'_>>,
     tokens: &mut Vec<Token>,
     mut line: usize,
-) -> usize {
+) -> Option<usize> {
     let mut string_val = String::new();
     loop {
         match chars.next() {
@@ -231,12 +245,10 @@ fn string_lookahead(
             Some(c) => {
                 string_val.push(c);
             }
-            None => {
-                panic!("Error")
-            }
+            None => return None,
         }
     }
-    return line;
+    return Some(line);
 }
 
 fn symbol_lookahead(
@@ -284,3 +296,51 @@ fn symbol_lookahead(
     }
     line
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex(source: &str) -> (Vec<TokenType>, Vec<String>) {
+        let (tokens, errors) = Lexer::from_source(source.to_string()).tokens();
+        (tokens.into_iter().map(|t| t.token_type).collect(), errors)
+    }
+
+    #[test]
+    fn unterminated_strings_are_errors() {
+        let (tokens, errors) = lex("print\n\"abc\ndef");
+        assert_eq!(tokens, [TokenType::Print, TokenType::Eof]);
+        assert_eq!(errors, ["[line 2] Error: Unterminated string."]);
+    }
+
+    #[test]
+    fn unexpected_characters_are_errors() {
+        let (tokens, errors) = lex("1 @ 2");
+        assert_eq!(
+            tokens,
+            [
+                TokenType::Number { literal: 1.0 },
+                TokenType::Number { literal: 2.0 },
+                TokenType::Eof,
+            ]
+        );
+        assert_eq!(errors, ["[line 1] Error: Unexpected character."]);
+    }
+
+    #[test]
+    fn numbers_take_one_decimal_point() {
+        let (tokens, errors) = lex("1.5.2 3.");
+        assert_eq!(
+            tokens,
+            [
+                TokenType::Number { literal: 1.5 },
+                TokenType::Dot,
+                TokenType::Number { literal: 2.0 },
+                TokenType::Number { literal: 3.0 },
+                TokenType::Dot,
+                TokenType::Eof,
+            ]
+        );
+        assert!(errors.is_empty());
+    }
+}
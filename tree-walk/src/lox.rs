@@ -1,29 +1,98 @@
-use crate::environment::Environment;
+use std::io::Write;
+
 use crate::error::Result;
+use crate::error::parse_error;
 use crate::eval::Eval;
+use crate::eval::Interpreter;
+use crate::eval::Truthiness;
 use crate::lexer::Lexer;
 use crate::parser::Parser;
 use crate::program::Program;
+use crate::resolver::Resolver;
+
+/// How to run a program, and what to say about it along the way.
+/// Diagnostics all go to stderr.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Options {
+    pub truthiness: Truthiness,
+    /// Print each token before parsing.
+    pub dump_tokens: bool,
+    /// Print the AST before running it.
+    pub dump_ast: bool,
+    /// Print the environment on every variable access.
+    pub trace_env: bool,
+}
 
-pub struct Lox {
+pub struct Lox<'out> {
     program: Program,
-    environment: Environment,
+    interpreter: Interpreter<'out>,
 }
 
-impl Lox {
-    pub fn new_from_input(input: String) -> Result<Self> {
+impl<'out> Lox<'out> {
+    /// Parse `input` into a program that prints to `output` when it's
+    /// run, or report every error in it that can be found without
+    /// running it.
+    pub fn new_from_input(
+        input: String,
+        options: Options,
+        output: impl Write + 'out,
+    ) -> Result<Self> {
         let lexer = Lexer::from_source(input);
-        let mut parser = Parser::from_tokens(lexer.tokens());
-        let program = parser.parse()?;
-        println!("{:?}", program);
+        let (tokens, mut errors) = lexer.tokens();
+        if options.dump_tokens {
+            for token in &tokens {
+                eprintln!("{:?}", token);
+            }
+        }
+        let mut parser = Parser::from_tokens(tokens);
+        let parsed = parser.parse();
+        if let Err(e) = &parsed {
+            errors.extend(e.to_string().lines().map(str::to_string));
+        }
+        // the lexer and parser each report in order, but together they
+        // should read top to bottom
+        errors.sort_by_key(|error| line_of(error));
+        if !errors.is_empty() {
+            return Err(parse_error::<Program>(&errors.join("\n")));
+        }
+        let program = parsed?;
+        Resolver::new().resolve(&program)?;
+        if options.dump_ast {
+            eprintln!("{:?}", program);
+        }
         Ok(Lox {
             program,
-            environment: Environment::new(),
+            interpreter: Interpreter::new(Box::new(output), options.truthiness, options.trace_env),
         })
     }
 
     pub fn eval(&mut self) -> Result<()> {
-        self.program.eval(&mut self.environment);
+        self.program.eval(&mut self.interpreter)?;
         Ok(())
     }
 }
+
+/// The line an error message like "[line 3] Error: ..." is about.
+fn line_of(error: &str) -> usize {
+    error
+        .strip_prefix("[line ")
+        .and_then(|rest| rest.split_once(']'))
+        .and_then(|(line, _)| line.parse().ok())
+        .unwrap_or(usize::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn print_writes_only_values_to_the_given_sink() {
+        let mut output = Vec::new();
+        let source = "var a = 1; print a + 1; a; print \"text\"; print nil;";
+        let mut lox =
+            Lox::new_from_input(source.to_string(), Options::default(), &mut output).unwrap();
+        lox.eval().unwrap();
+        drop(lox);
+        assert_eq!(String::from_utf8(output).unwrap(), "2\ntext\nnil\n");
+    }
+}
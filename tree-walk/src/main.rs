@@ -3,22 +3,67 @@ use std::fs::File;
 use std::io;
 use std::io::Read;
 use std::io::Write;
+use std::thread;
 
 mod lox;
 use lox::Lox;
+use lox::Options;
+mod class;
 mod environment;
 mod error;
 mod eval;
+use eval::Truthiness;
 mod lexer;
 mod parser;
 mod program;
+mod resolver;
 mod scanner;
 
-fn main() -> error::Result<()> {
-    let args: Vec<String> = env::args().collect();
+/// Stack for the thread running the interpreter, which recurses once
+/// per nested call and expression: enough for the call depth cap even
+/// in a debug build.
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+fn main() {
+    let interpreter = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(|| {
+            if let Err(e) = lox_main() {
+                eprintln!("Error: {:?}", e);
+                std::process::exit(1);
+            }
+        })
+        .unwrap();
+    if interpreter.join().is_err() {
+        std::process::exit(101);
+    }
+}
+
+fn lox_main() -> error::Result<()> {
+    let mut options = Options::default();
+    let mut args = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--reference" => options.truthiness = Truthiness::Reference,
+            "--dump-tokens" => options.dump_tokens = true,
+            "--dump-ast" => options.dump_ast = true,
+            "--trace-env" => options.trace_env = true,
+            flag if flag.starts_with("--") => {
+                print_usage();
+                std::process::exit(1);
+            }
+            _ => args.push(arg),
+        }
+    }
     match &args[..] {
-        [_] => run_prompt(),
-        [_, script] => run_file(script),
+        [] => run_prompt(options),
+        [script] => {
+            if let Err(e) = run_file(script, options) {
+                eprintln!("{}", e);
+                std::process::exit(exit_code(&*e));
+            }
+            Ok(())
+        }
         _ => {
             print_usage();
             std::process::exit(1);
@@ -26,18 +71,34 @@ fn main() -> error::Result<()> {
     }
 }
 
+/// The status to exit with after `e`, following the book's use of
+/// the sysexits codes.
+fn exit_code(e: &(dyn std::error::Error + 'static)) -> i32 {
+    match e.downcast_ref::<error::Error>() {
+        Some(error::Error::ParseError(_)) => 65,
+        Some(error::Error::RuntimeError(_)) => 70,
+        None => 74,
+    }
+}
+
 fn print_usage() {
-    println!("Usage: lox1 [script]");
+    println!("Usage: lox1 [options] [script]");
+    println!();
+    println!("  --reference    treat nil and false as false and anything else as true,");
+    println!("                 as in the book, rather than only accepting bools");
+    println!("  --dump-tokens  print the tokens to stderr before parsing");
+    println!("  --dump-ast     print the AST to stderr before running it");
+    println!("  --trace-env    print the environment to stderr on every variable access");
 }
 
-fn run_file(script: &String) -> error::Result<()> {
+fn run_file(script: &String, options: Options) -> error::Result<()> {
     let mut file = File::open(script)?;
     let mut buffer = String::new();
     file.read_to_string(&mut buffer)?;
-    run(buffer)
+    run(buffer, options)
 }
 
-fn run_prompt() -> error::Result<()> {
+fn run_prompt(options: Options) -> error::Result<()> {
     let stdin = io::stdin();
     loop {
         print!("> ");
@@ -48,15 +109,15 @@ fn run_prompt() -> error::Result<()> {
         if buffer.is_empty() {
             break;
         }
-        if let Err(e) = run(buffer) {
-            println!("Error: {:?}", e);
+        if let Err(e) = run(buffer, options) {
+            println!("Error: {}", e);
         }
     }
     Ok(())
 }
 
-fn run(source: String) -> error::Result<()> {
-    match Lox::new_from_input(source) {
+fn run(source: String, options: Options) -> error::Result<()> {
+    match Lox::new_from_input(source, options, io::stdout()) {
         Ok(mut lox) => lox.eval(),
         Err(e) => Err(e),
     }
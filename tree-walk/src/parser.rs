@@ -1,39 +1,104 @@
-use crate::error::{Result, eof_parse_error, parse_error};
+use std::rc::Rc;
+
+use crate::error::{Error, Result, eof_parse_error, parse_error};
 use crate::lexer::{Token, TokenType};
 use crate::program::{
-    BinaryOp, Declaration, Expr, Literal, LogicalOp, Program, Statement, UnaryOp, binop,
+    BinaryOp, Class, Declaration, Depth, Expr, Function, Literal, LogicalOp, Program, Statement,
+    UnaryOp, binop,
 };
 
+/// The most arguments a call can pass, or parameters a function can
+/// take, as in the book.
+const MAX_ARITY: usize = 255;
+
 pub struct Parser {
     pub tokens: Vec<Token>,
     current: usize,
+    /// Every error found so far, as it will be reported.
+    errors: Vec<String>,
 }
 
 impl Parser {
     /// Given an iterator of tokens, construct a parser for those
     /// tokens.
     pub fn from_tokens(tokens: impl IntoIterator<Item = Token>) -> Self {
-        let p = Parser {
+        Parser {
             tokens: tokens.into_iter().collect(),
             current: 0,
-        };
-        for token in &p.tokens {
-            println!("{:?}", token);
+            errors: Vec::new(),
         }
-        p
     }
 
     /// The main entry point to try to parse the provided sequence of
     /// tokens.  Either succeeds and gives you a program, or else
-    /// returns a parse error.
+    /// returns a parse error listing everything that's wrong with it,
+    /// one error per line.
     pub fn parse(&mut self) -> Result<Program> {
         let mut decls = Vec::new();
-        loop {
-            if let Some(TokenType::Eof) = self.peek_token_type() {
-                return Ok(decls);
-            } else {
-                let stmt = self.declaration()?;
-                decls.push(stmt);
+        while !matches!(self.peek_token_type(), Some(TokenType::Eof) | None) {
+            if let Some(decl) = self.recovering_declaration() {
+                decls.push(decl);
+            }
+        }
+        if self.errors.is_empty() {
+            Ok(decls)
+        } else {
+            Err(parse_error::<Program>(&self.errors.join("\n")))
+        }
+    }
+
+    /// An error at the current token, laid out the way the book
+    /// reports them.
+    fn error(&self, msg: &str) -> Box<Error> {
+        let token = self.tokens.get(self.current).or(self.tokens.last());
+        let (line, at) = match token {
+            Some(token) if token.token_type == TokenType::Eof => (token.line, "end".to_string()),
+            Some(token) => (token.line, format!("'{}'", token.lexeme)),
+            None => (0, "end".to_string()),
+        };
+        parse_error::<()>(&format!("[line {}] Error at {}: {}", line, at, msg))
+    }
+
+    /// A declaration, or `None` if it's malformed, in which case the
+    /// error is recorded and the tokens are skipped up to where the
+    /// next declaration probably starts.
+    fn recovering_declaration(&mut self) -> Option<Declaration> {
+        match self.declaration() {
+            Ok(decl) => Some(decl),
+            Err(e) => {
+                // an unclosed block is reported again by each block
+                // around it, so only keep one of the same error
+                let error = e.to_string();
+                if self.errors.last() != Some(&error) {
+                    self.errors.push(error);
+                }
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    /// Skip past the token an error was found at, and on to the end of
+    /// the statement it's in or the start of the next one.
+    fn synchronize(&mut self) {
+        self.consume();
+        while let Some(token_type) = self.peek_token_type() {
+            let after_semicolon = self.current > 0
+                && self.tokens[self.current - 1].token_type == TokenType::SemiColon;
+            if after_semicolon {
+                return;
+            }
+            match token_type {
+                TokenType::Eof
+                | TokenType::Class
+                | TokenType::Fun
+                | TokenType::Var
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return => return,
+                _ => self.consume(),
             }
         }
     }
@@ -51,9 +116,11 @@ impl Parser {
             .ok_or(eof_parse_error::<&Token>())
     }
 
-    /// Move forward in the sequence of tokens
+    /// Move forward in the sequence of tokens, stopping at the end
     fn consume(&mut self) {
-        self.current += 1;
+        if !matches!(self.peek_token_type(), Some(TokenType::Eof) | None) {
+            self.current += 1;
+        }
     }
 
     /// Try to consume a token of a particular type.  For instance,
@@ -71,25 +138,31 @@ impl Parser {
     fn declaration(&mut self) -> Result<Declaration> {
         match self.peek_token_type() {
             Some(TokenType::Var) => self.var_declaration(),
+            Some(TokenType::Fun) => self.fun_declaration(),
+            Some(TokenType::Class) => self.class_declaration(),
             _ => Ok(Declaration::Statement(self.statement()?)),
         }
     }
 
     fn var_declaration(&mut self) -> Result<Declaration> {
         self.consume_type(TokenType::Var);
-        let ident = self.current_token().unwrap().lexeme.clone();
-
-        // TODO: Because the data is owned by the enum variant, it's
-        // awkard to match it by type.
+        let line = self.current_token()?.line;
+        let ident = match self.peek_token_type() {
+            Some(TokenType::Identifier { name }) => name.clone(),
+            _ => return Err(self.error("Expect variable name.")),
+        };
         self.consume();
 
         match self.peek_token_type() {
             Some(TokenType::Equal) => {
                 self.consume();
                 let rhs = self.expr()?;
-                self.consume_type(TokenType::SemiColon).unwrap();
+                if self.consume_type(TokenType::SemiColon).is_none() {
+                    return Err(self.error("Expect ';' after variable declaration."));
+                }
                 Ok(Declaration::Variable {
                     identifier: ident,
+                    line,
                     value: Some(rhs),
                 })
             }
@@ -97,27 +170,143 @@ impl Parser {
                 self.consume();
                 Ok(Declaration::Variable {
                     identifier: ident,
+                    line,
                     value: None,
                 })
             }
-            _ => Err(parse_error::<Declaration>("Malformed variable declaration")),
+            _ => Err(self.error("Expect ';' after variable declaration.")),
+        }
+    }
+
+    fn fun_declaration(&mut self) -> Result<Declaration> {
+        self.consume_type(TokenType::Fun);
+        Ok(Declaration::Function(Rc::new(self.function(false)?)))
+    }
+
+    fn class_declaration(&mut self) -> Result<Declaration> {
+        self.consume_type(TokenType::Class);
+        let line = self.current_token()?.line;
+        let name = match self.peek_token_type() {
+            Some(TokenType::Identifier { name }) => name.clone(),
+            _ => return Err(self.error("Expect class name.")),
+        };
+        self.consume();
+
+        let mut superclass = None;
+        if let Some(TokenType::Less) = self.peek_token_type() {
+            self.consume();
+            let token = self.current_token()?.clone();
+            if !matches!(token.token_type, TokenType::Identifier { .. }) {
+                return Err(self.error("Expect superclass name."));
+            }
+            self.consume();
+            superclass = Some(Expr::Literal(Literal::Identifier(token, Depth::default())));
+        }
+
+        if self.consume_type(TokenType::LeftBrace).is_none() {
+            return Err(self.error("Expect '{' before class body."));
+        }
+        let mut methods = Vec::new();
+        loop {
+            match self.peek_token_type() {
+                Some(TokenType::RightBrace) => {
+                    self.consume();
+                    break;
+                }
+                Some(TokenType::Eof) | None => {
+                    return Err(self.error("Expect '}' after class body."));
+                }
+                _ => methods.push(Rc::new(self.function(true)?)),
+            }
+        }
+        Ok(Declaration::Class(Class {
+            name,
+            line,
+            superclass,
+            methods,
+        }))
+    }
+
+    /// A function's name, parameters and body, after any `fun`.
+    fn function(&mut self, method: bool) -> Result<Function> {
+        let line = self.current_token()?.line;
+        let name = match self.peek_token_type() {
+            Some(TokenType::Identifier { name }) => name.clone(),
+            _ => return Err(self.error("Expect function name.")),
+        };
+        self.consume();
+
+        if self.consume_type(TokenType::LeftParen).is_none() {
+            return Err(self.error("Expect '(' after function name."));
+        }
+        let mut params = Vec::new();
+        if let Some(TokenType::RightParen) = self.peek_token_type() {
+            self.consume();
+        } else {
+            loop {
+                if params.len() >= MAX_ARITY {
+                    return Err(self.error("Can't have more than 255 parameters."));
+                }
+                match self.peek_token_type() {
+                    Some(TokenType::Identifier { name }) => params.push(name.clone()),
+                    _ => return Err(self.error("Expect parameter name.")),
+                }
+                self.consume();
+                match self.peek_token_type() {
+                    Some(TokenType::Comma) => self.consume(),
+                    Some(TokenType::RightParen) => {
+                        self.consume();
+                        break;
+                    }
+                    _ => return Err(self.error("Expect ')' after parameters.")),
+                }
+            }
+        }
+
+        if self.consume_type(TokenType::LeftBrace).is_none() {
+            return Err(self.error("Expect '{' before function body."));
+        }
+        let body = self.block()?;
+        Ok(Function {
+            initializer: method && name == "init",
+            name,
+            line,
+            params,
+            body,
+        })
+    }
+
+    /// The declarations in a block, up to and including the closing
+    /// brace.  The opening brace has already been consumed.
+    fn block(&mut self) -> Result<Vec<Declaration>> {
+        let mut decls = Vec::new();
+        loop {
+            match self.peek_token_type() {
+                Some(TokenType::Eof) | None => return Err(self.error("Expect '}' after block.")),
+                Some(TokenType::RightBrace) => {
+                    self.consume();
+                    return Ok(decls);
+                }
+                _ => {
+                    if let Some(decl) = self.recovering_declaration() {
+                        decls.push(decl);
+                    }
+                }
+            }
         }
     }
 
     fn statement(&mut self) -> Result<Statement> {
         match self.peek_token_type() {
             Some(TokenType::If) => {
+                let line = self.current_token()?.line;
                 self.consume();
                 if let None = self.consume_type(TokenType::LeftParen) {
-                    return Err(parse_error::<Statement>(
-                        "Expected parenthesized expression after if statement",
-                    ));
+                    return Err(self.error("Expect '(' after 'if'."));
                 }
                 let cond = self.expr()?;
                 if let None = self.consume_type(TokenType::RightParen) {
-                    return Err(parse_error::<Statement>(
-                        "Reached the end with an if expression not parenthesized correctly",
-                    ));
+                    return Err(self.error("Expect ')' after if condition."));
                 }
                 let then_branch = self.statement()?;
                 let mut else_branch = None;
@@ -129,6 +318,7 @@ impl Parser {
                     cond,
                     Box::new(then_branch),
                     Box::new(else_branch),
+                    line,
                 ))
             }
             Some(TokenType::For) => {
@@ -138,44 +328,47 @@ impl Parser {
                 return self.while_statement();
             }
             Some(TokenType::Print) => {
+                let line = self.current_token()?.line;
                 self.consume();
                 let rhs = self.expr()?;
-                self.consume_type(TokenType::SemiColon);
-                Ok(Statement::Print(rhs))
+                if self.consume_type(TokenType::SemiColon).is_none() {
+                    return Err(self.error("Expect ';' after value."));
+                }
+                Ok(Statement::Print(rhs, line))
             }
-            Some(TokenType::Eof) | None => Err(eof_parse_error::<Statement>()),
+            Some(TokenType::Eof) | None => Err(self.error("Expect expression.")),
             Some(TokenType::LeftBrace) => {
                 self.consume();
-                let mut decls = Vec::new();
-                loop {
-                    match self.peek_token_type() {
-                        Some(TokenType::Eof) => todo!(),
-                        Some(TokenType::RightBrace) => {
-                            self.consume();
-                            return Ok(Statement::Block(decls));
-                        }
-                        _ => {
-                            let decl = self.declaration()?;
-                            decls.push(decl);
-                        }
-                    }
+                Ok(Statement::Block(self.block()?))
+            }
+            Some(TokenType::Return) => {
+                let keyword = self.current_token()?.clone();
+                self.consume();
+                let value = match self.peek_token_type() {
+                    Some(TokenType::SemiColon) => None,
+                    _ => Some(self.expr()?),
+                };
+                match self.consume_type(TokenType::SemiColon) {
+                    Some(_) => Ok(Statement::Return(keyword, value)),
+                    _ => Err(self.error("Expect ';' after return value.")),
                 }
             }
             Some(_) => {
                 let rhs = self.expr()?;
                 match self.consume_type(TokenType::SemiColon) {
                     Some(_) => Ok(Statement::Expr(rhs)),
-                    _ => Err(parse_error::<Statement>("Missing semicolon")),
+                    _ => Err(self.error("Expect ';' after expression.")),
                 }
             }
         }
     }
 
     fn for_statement(&mut self) -> Result<Statement> {
+        let line = self.current_token()?.line;
         self.consume_type(TokenType::For);
 
         if let None = self.consume_type(TokenType::LeftParen) {
-            return Err(parse_error::<Statement>("Expected '(' after 'for'."));
+            return Err(self.error("Expect '(' after 'for'."));
         }
 
         let initializer = if let Some(TokenType::SemiColon) = self.peek_token_type() {
@@ -186,9 +379,7 @@ impl Parser {
         } else {
             let expr = self.expr()?;
             if let None = self.consume_type(TokenType::SemiColon) {
-                return Err(parse_error::<Statement>(
-                    "Expected ';' after loop initializer.",
-                ));
+                return Err(self.error("Expect ';' after loop initializer."));
             }
             Some(Box::new(Declaration::Statement(Statement::Expr(expr))))
         };
@@ -202,9 +393,7 @@ impl Parser {
         };
 
         if let None = self.consume_type(TokenType::SemiColon) {
-            return Err(parse_error::<Statement>(
-                "Expected ';' after loop condition.",
-            ));
+            return Err(self.error("Expect ';' after loop condition."));
         }
 
         let increment = if let Some(TokenType::RightParen) = self.peek_token_type() {
@@ -215,7 +404,7 @@ impl Parser {
         };
 
         if let None = self.consume_type(TokenType::RightParen) {
-            return Err(parse_error::<Statement>("Expected ')' after for clauses."));
+            return Err(self.error("Expect ')' after for clauses."));
         }
 
         let body = self.statement()?;
@@ -225,16 +414,22 @@ impl Parser {
             condition,
             increment,
             Box::new(body),
+            line,
         ));
     }
 
     fn while_statement(&mut self) -> Result<Statement> {
+        let line = self.current_token()?.line;
         self.consume_type(TokenType::While);
-        self.consume_type(TokenType::LeftParen);
+        if self.consume_type(TokenType::LeftParen).is_none() {
+            return Err(self.error("Expect '(' after 'while'."));
+        }
         let cond = self.expr()?;
-        self.consume_type(TokenType::RightParen);
+        if self.consume_type(TokenType::RightParen).is_none() {
+            return Err(self.error("Expect ')' after condition."));
+        }
         let body = self.statement()?;
-        return Ok(Statement::While(cond, Box::new(body)));
+        return Ok(Statement::While(cond, Box::new(body), line));
     }
 
     fn expr(&mut self) -> Result<Expr> {
@@ -245,69 +440,51 @@ impl Parser {
         let lhs = self.or()?;
 
         if let Some(TokenType::Equal) = self.peek_token_type() {
+            // reported on the `=`, but there's no need to resynchronize
+            let invalid_target = self.error("Invalid assignment target.");
             self.consume_type(TokenType::Equal);
             let rhs = self.assignment()?;
 
-            if let Expr::Literal(Literal::Identifier(name)) = lhs {
-                return Ok(Expr::Assignment(
-                    // Creating a fake token here to deal with lvalues
-                    // is ugly, but in sticking with the book
-                    Token {
-                        token_type: TokenType::Identifier { name: name.clone() },
-                        line: self.current_token()?.line,
-                        lexeme: name,
-                    },
-                    Box::new(rhs),
-                ));
+            match lhs {
+                Expr::Literal(Literal::Identifier(name, depth)) => {
+                    return Ok(Expr::Assignment(name, Box::new(rhs), depth));
+                }
+                Expr::Get(object, name) => return Ok(Expr::Set(object, name, Box::new(rhs))),
+                _ => self.errors.push(invalid_target.to_string()),
             }
         }
         return Ok(lhs);
     }
 
     fn or(&mut self) -> Result<Expr> {
-        let lhs = self.and()?;
-
-        loop {
-            match self.peek_token_type() {
-                Some(TokenType::Or) => {
-                    self.consume();
-                    let rhs = self.and()?;
-                    return Ok(Expr::Logical(Box::new(lhs), LogicalOp::Or, Box::new(rhs)));
-                }
-                _ => {
-                    return Ok(lhs);
-                }
-            }
+        let mut lhs = self.and()?;
+        while let Some(TokenType::Or) = self.peek_token_type() {
+            let line = self.current_token()?.line;
+            self.consume();
+            let rhs = self.and()?;
+            lhs = Expr::Logical(Box::new(lhs), LogicalOp::Or, Box::new(rhs), line);
         }
+        Ok(lhs)
     }
 
     fn and(&mut self) -> Result<Expr> {
-        let lhs = self.equality()?;
-
-        loop {
-            match self.peek_token_type() {
-                Some(TokenType::And) => {
-                    self.consume();
-                    let rhs = self.equality()?;
-                    return Ok(Expr::Logical(Box::new(lhs), LogicalOp::And, Box::new(rhs)));
-                }
-                _ => {
-                    return Ok(lhs);
-                }
-            }
+        let mut lhs = self.equality()?;
+        while let Some(TokenType::And) = self.peek_token_type() {
+            let line = self.current_token()?.line;
+            self.consume();
+            let rhs = self.equality()?;
+            lhs = Expr::Logical(Box::new(lhs), LogicalOp::And, Box::new(rhs), line);
         }
+        Ok(lhs)
     }
 
     fn equality(&mut self) -> Result<Expr> {
-        let lhs = self.comparison()?;
-        loop {
-            if let Some(op) = self.to_equality_op() {
-                self.consume();
-                let rhs = self.comparison()?;
-                return Ok(binop(lhs, op, rhs));
-            } else {
-                break;
-            }
+        let mut lhs = self.comparison()?;
+        while let Some(op) = self.to_equality_op() {
+            let line = self.current_token()?.line;
+            self.consume();
+            let rhs = self.comparison()?;
+            lhs = binop(lhs, op, rhs, line);
         }
         Ok(lhs)
     }
@@ -322,15 +499,12 @@ impl Parser {
     }
 
     fn comparison(&mut self) -> Result<Expr> {
-        let lhs = self.term()?;
-        loop {
-            if let Some(op) = self.to_comparison_op() {
-                self.consume();
-                let rhs = self.term()?;
-                return Ok(binop(lhs, op, rhs));
-            } else {
-                break;
-            }
+        let mut lhs = self.term()?;
+        while let Some(op) = self.to_comparison_op() {
+            let line = self.current_token()?.line;
+            self.consume();
+            let rhs = self.term()?;
+            lhs = binop(lhs, op, rhs, line);
         }
         Ok(lhs)
     }
@@ -347,15 +521,12 @@ impl Parser {
     }
 
     fn term(&mut self) -> Result<Expr> {
-        let lhs = self.factor()?;
-        loop {
-            if let Some(op) = self.to_term_op() {
-                self.consume();
-                let rhs = self.factor()?;
-                return Ok(binop(lhs, op, rhs));
-            } else {
-                break;
-            }
+        let mut lhs = self.factor()?;
+        while let Some(op) = self.to_term_op() {
+            let line = self.current_token()?.line;
+            self.consume();
+            let rhs = self.factor()?;
+            lhs = binop(lhs, op, rhs, line);
         }
         Ok(lhs)
     }
@@ -370,15 +541,12 @@ impl Parser {
     }
 
     fn factor(&mut self) -> Result<Expr> {
-        let lhs = self.unary()?;
-        loop {
-            if let Some(op) = self.to_factor_op() {
-                self.consume();
-                let rhs = self.unary()?;
-                return Ok(binop(lhs, op, rhs));
-            } else {
-                break;
-            }
+        let mut lhs = self.unary()?;
+        while let Some(op) = self.to_factor_op() {
+            let line = self.current_token()?.line;
+            self.consume();
+            let rhs = self.unary()?;
+            lhs = binop(lhs, op, rhs, line);
         }
         Ok(lhs)
     }
@@ -394,14 +562,60 @@ impl Parser {
 
     fn unary(&mut self) -> Result<Expr> {
         if let Some(op) = self.to_unary_op() {
+            let line = self.current_token()?.line;
             self.consume();
             let rhs = self.unary()?;
-            Ok(Expr::Unary(op, Box::new(rhs)))
+            Ok(Expr::Unary(op, Box::new(rhs), line))
         } else {
-            self.primary()
+            self.call()
+        }
+    }
+
+    fn call(&mut self) -> Result<Expr> {
+        let mut expr = self.primary()?;
+        loop {
+            match self.peek_token_type() {
+                Some(TokenType::LeftParen) => {
+                    self.consume();
+                    expr = self.finish_call(expr)?;
+                }
+                Some(TokenType::Dot) => {
+                    self.consume();
+                    let name = self.current_token()?.clone();
+                    if !matches!(name.token_type, TokenType::Identifier { .. }) {
+                        return Err(self.error("Expect property name after '.'."));
+                    }
+                    self.consume();
+                    expr = Expr::Get(Box::new(expr), name);
+                }
+                _ => return Ok(expr),
+            }
         }
     }
 
+    /// The arguments of a call to `callee`, after the opening paren.
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr> {
+        let mut args = Vec::new();
+        if !matches!(self.peek_token_type(), Some(TokenType::RightParen)) {
+            loop {
+                if args.len() >= MAX_ARITY {
+                    return Err(self.error("Can't have more than 255 arguments."));
+                }
+                args.push(self.expr()?);
+                match self.peek_token_type() {
+                    Some(TokenType::Comma) => self.consume(),
+                    _ => break,
+                }
+            }
+        }
+        // the book reports errors in a call on its closing paren
+        let line = self.current_token()?.line;
+        if self.consume_type(TokenType::RightParen).is_none() {
+            return Err(self.error("Expect ')' after arguments."));
+        }
+        Ok(Expr::Call(Box::new(callee), args, line))
+    }
+
     fn to_unary_op(&mut self) -> Option<UnaryOp> {
         let token = self.current_token().ok()?;
         match &token.token_type {
@@ -412,7 +626,7 @@ impl Parser {
     }
 
     fn primary(&mut self) -> Result<Expr> {
-        let token = self.current_token().unwrap();
+        let token = self.current_token()?;
         match &token.token_type {
             TokenType::False => {
                 self.consume();
@@ -441,18 +655,215 @@ impl Parser {
                 let expr = self.expr()?;
                 match self.consume_type(TokenType::RightParen) {
                     Some(_) => Ok(Expr::Grouping(Box::new(expr))),
-                    _ => Err(parse_error::<Expr>("Failed to find expected closing paren")),
+                    _ => Err(self.error("Expect ')' after expression.")),
                 }
             }
-            TokenType::Identifier { name } => {
-                let id = name.clone();
+            TokenType::This => {
+                let keyword = token.clone();
                 self.consume();
-                Ok(Expr::Literal(Literal::Identifier(id)))
+                Ok(Expr::This(keyword, Depth::default()))
             }
-            _ => Err(parse_error::<Expr>(&format!(
-                "Line {:?}: Unable to parse expression {:?} as token of type {:?}",
-                token.line, token.lexeme, token.token_type,
-            ))),
+            TokenType::Super => {
+                let keyword = token.clone();
+                self.consume();
+                if self.consume_type(TokenType::Dot).is_none() {
+                    return Err(self.error("Expect '.' after 'super'."));
+                }
+                let method = self.current_token()?.clone();
+                if !matches!(method.token_type, TokenType::Identifier { .. }) {
+                    return Err(self.error("Expect superclass method name."));
+                }
+                self.consume();
+                Ok(Expr::Super(keyword, method, Depth::default()))
+            }
+            TokenType::Identifier { .. } => {
+                let id = token.clone();
+                self.consume();
+                Ok(Expr::Literal(Literal::Identifier(id, Depth::default())))
+            }
+            _ => Err(self.error("Expect expression.")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    /// The AST of the expression statement `source`, as printed.
+    fn ast(source: &str) -> String {
+        let lexer = Lexer::from_source(format!("{source};"));
+        let (tokens, _) = lexer.tokens();
+        let program = Parser::from_tokens(tokens).parse().unwrap();
+        match &program[..] {
+            [Declaration::Statement(Statement::Expr(expr))] => format!("{:?}", expr),
+            _ => panic!("{source} isn't a single expression: {:?}", program),
+        }
+    }
+
+    /// The errors parsing `source` reports.
+    fn errors(source: &str) -> Vec<String> {
+        let (tokens, _) = Lexer::from_source(source.to_string()).tokens();
+        match Parser::from_tokens(tokens).parse() {
+            Ok(program) => panic!("{source} parsed as {:?}", program),
+            Err(e) => e.to_string().lines().map(str::to_string).collect(),
+        }
+    }
+
+    #[test]
+    fn binary_operators_are_left_associative() {
+        assert_eq!(ast("1 - 2 - 3"), "(- (- 1 2) 3)");
+        assert_eq!(ast("1 + 2 - 3 + 4"), "(+ (- (+ 1 2) 3) 4)");
+        assert_eq!(ast("8 / 4 / 2"), "(/ (/ 8 4) 2)");
+        assert_eq!(ast("2 * 3 / 4 * 5"), "(* (/ (* 2 3) 4) 5)");
+        assert_eq!(ast("1 < 2 < 3"), "(< (< 1 2) 3)");
+        assert_eq!(ast("1 >= 2 <= 3 > 4"), "(> (<= (>= 1 2) 3) 4)");
+        assert_eq!(ast("1 == 2 != 3 == 4"), "(== (!= (== 1 2) 3) 4)");
+    }
+
+    #[test]
+    fn logical_operators_are_left_associative() {
+        assert_eq!(ast("a and b and c"), "(and (and a b) c)");
+        assert_eq!(ast("a or b or c"), "(or (or a b) c)");
+    }
+
+    #[test]
+    fn unary_operators_are_right_associative() {
+        assert_eq!(ast("!!a"), "(! (! a))");
+        assert_eq!(ast("- -1"), "(- (- 1))");
+    }
+
+    #[test]
+    fn assignment_is_right_associative() {
+        assert_eq!(ast("a = b = 1"), "(= a (= b 1))");
+        assert_eq!(ast("a = b or c"), "(= a (or b c))");
+    }
+
+    #[test]
+    fn operators_bind_by_precedence() {
+        assert_eq!(ast("1 + 2 * 3"), "(+ 1 (* 2 3))");
+        assert_eq!(ast("1 * 2 + 3"), "(+ (* 1 2) 3)");
+        assert_eq!(ast("1 - 2 / 3 - 4"), "(- (- 1 (/ 2 3)) 4)");
+        assert_eq!(ast("-1 * 2"), "(* (- 1) 2)");
+        assert_eq!(ast("1 + 2 < 3 * 4"), "(< (+ 1 2) (* 3 4))");
+        assert_eq!(ast("1 < 2 == 3 > 4"), "(== (< 1 2) (> 3 4))");
+        assert_eq!(ast("a == b and c != d"), "(and (== a b) (!= c d))");
+        assert_eq!(ast("a or b and c"), "(or a (and b c))");
+        assert_eq!(ast("a and b or c and d"), "(or (and a b) (and c d))");
+        assert_eq!(ast("!a == b"), "(== (! a) b)");
+    }
+
+    #[test]
+    fn grouping_overrides_precedence() {
+        assert_eq!(ast("(1 + 2) * 3"), "(* (group (+ 1 2)) 3)");
+        assert_eq!(ast("1 - (2 - 3)"), "(- 1 (group (- 2 3)))");
+        assert_eq!(ast("a and (b or c)"), "(and a (group (or b c)))");
+    }
+
+    #[test]
+    fn calls_and_properties_bind_tightest() {
+        assert_eq!(ast("-f(1) * a.b"), "(* (- (call f 1)) (. a b))");
+        assert_eq!(ast("f(1)(2)"), "(call (call f 1) 2)");
+        assert_eq!(ast("a.b.c = 1 + 2"), "(.= (. a b) c (+ 1 2))");
+    }
+
+    #[test]
+    fn reports_every_error_with_its_line() {
+        assert_eq!(
+            errors("var = 1;\nprint 1 +;\nprint 2;\n{ var b = ; print b; }\n1 = 2;"),
+            [
+                "[line 1] Error at '=': Expect variable name.",
+                "[line 2] Error at ';': Expect expression.",
+                "[line 4] Error at ';': Expect expression.",
+                "[line 5] Error at '=': Invalid assignment target.",
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_semicolons_are_reported_where_they_were_expected() {
+        assert_eq!(
+            errors("var a = 1\nprint a;\nprint a\nprint a;"),
+            [
+                "[line 2] Error at 'print': Expect ';' after variable declaration.",
+                "[line 4] Error at 'print': Expect ';' after value.",
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_are_worded_as_the_book_does() {
+        let cases = [
+            ("var false = 1;", "Error at 'false': Expect variable name."),
+            (
+                "var a 1;",
+                "Error at '1': Expect ';' after variable declaration.",
+            ),
+            (
+                "var a = 1 2;",
+                "Error at '2': Expect ';' after variable declaration.",
+            ),
+            ("1 + 2", "Error at end: Expect ';' after expression."),
+            ("a = 1 b;", "Error at 'b': Expect ';' after expression."),
+            ("print 1 2;", "Error at '2': Expect ';' after value."),
+            (
+                "print (1 + 2;",
+                "Error at ';': Expect ')' after expression.",
+            ),
+            ("f(1, 2;", "Error at ';': Expect ')' after arguments."),
+            ("if 1) print 1;", "Error at '1': Expect '(' after 'if'."),
+            (
+                "if (1 print 1;",
+                "Error at 'print': Expect ')' after if condition.",
+            ),
+        ];
+        for (source, error) in cases {
+            assert_eq!(errors(source), [format!("[line 1] {error}")], "{source}");
+        }
+    }
+
+    #[test]
+    fn unterminated_blocks_are_reported_at_the_end() {
+        assert_eq!(
+            errors("fun f() {\n  print 1;"),
+            ["[line 2] Error at end: Expect '}' after block."]
+        );
+    }
+
+    #[test]
+    fn unclosed_blocks_are_reported_once() {
+        assert_eq!(
+            errors("{\n  if (true) {\n    print 1;"),
+            ["[line 3] Error at end: Expect '}' after block."]
+        );
+    }
+
+    #[test]
+    fn malformed_input_is_an_error_not_a_panic() {
+        let sources = [
+            "var",
+            "var x",
+            "print",
+            "{",
+            "if (true",
+            "while",
+            "for (",
+            "class A {",
+            "fun f(",
+            "print (1",
+            "a.",
+            "super",
+            "return",
+            "x =",
+            "!",
+            "f(1,",
+            "}",
+            ")",
+            ";",
+        ];
+        for source in sources {
+            errors(source);
         }
     }
 }
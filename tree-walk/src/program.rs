@@ -1,41 +1,95 @@
 //! Encodes the AST of the language
 
+use std::cell::Cell;
 use std::fmt;
+use std::rc::Rc;
 
 use crate::lexer::Token;
 
 pub type Program = Vec<Declaration>;
 
+/// How many scopes out from where a variable is used it was declared,
+/// or `None` for a global.  The resolver fills this in.
+pub type Depth = Cell<Option<usize>>;
+
 #[derive(Debug)]
 pub enum Declaration {
     Variable {
         identifier: String,
+        line: usize,
         value: Option<Expr>,
     },
+    Function(Rc<Function>),
+    Class(Class),
     Statement(Statement),
 }
 
+#[derive(Debug)]
+pub struct Class {
+    pub name: String,
+    pub line: usize,
+    /// Always a `Literal::Identifier`, so it can be resolved like any
+    /// other variable.
+    pub superclass: Option<Expr>,
+    pub methods: Vec<Rc<Function>>,
+}
+
+/// A function declaration.  It's shared with every function value
+/// made from it, which is why it sits behind an `Rc`.
+#[derive(Debug)]
+pub struct Function {
+    pub name: String,
+    pub line: usize,
+    pub params: Vec<String>,
+    pub body: Vec<Declaration>,
+    /// Whether this is a class's `init` method, which always returns
+    /// the instance.
+    pub initializer: bool,
+}
+
+// Functions are only ever equal to themselves, as in the book
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// Statements that can fail at runtime, and the expressions below,
+/// end with the line they're on, for the error message.
 #[derive(Debug)]
 pub enum Statement {
     Expr(Expr),
-    Print(Expr),
+    Print(Expr, usize),
     Block(Vec<Declaration>),
-    IfElse(Expr, Box<Statement>, Box<Option<Statement>>),
-    While(Expr, Box<Statement>),
-    For(Option<Box<Declaration>>, Option<Box<Expr>>, Option<Box<Expr>>, Box<Statement>),
+    IfElse(Expr, Box<Statement>, Box<Option<Statement>>, usize),
+    While(Expr, Box<Statement>, usize),
+    For(
+        Option<Box<Declaration>>,
+        Option<Box<Expr>>,
+        Option<Box<Expr>>,
+        Box<Statement>,
+        usize,
+    ),
+    Return(Token, Option<Expr>),
 }
 
 #[derive(Clone)]
 pub enum Expr {
-    Binary(Box<Expr>, BinaryOp, Box<Expr>),
-    Unary(UnaryOp, Box<Expr>),
-    Assignment(Token, Box<Expr>),
+    Binary(Box<Expr>, BinaryOp, Box<Expr>, usize),
+    Unary(UnaryOp, Box<Expr>, usize),
+    Assignment(Token, Box<Expr>, Depth),
     Literal(Literal),
     Grouping(Box<Expr>),
-    Logical(Box<Expr>, LogicalOp, Box<Expr>),
+    Logical(Box<Expr>, LogicalOp, Box<Expr>, usize),
+    Call(Box<Expr>, Vec<Expr>, usize),
+    Get(Box<Expr>, Token),
+    Set(Box<Expr>, Token, Box<Expr>),
+    This(Token, Depth),
+    // The `super` keyword and the method name
+    Super(Token, Token, Depth),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub enum LogicalOp {
     Or,
     And,
@@ -68,20 +122,33 @@ pub enum Literal {
     True,
     False,
     Nil,
-    Identifier(String),
+    Identifier(Token, Depth),
 }
 
 impl fmt::Debug for Expr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Expr::Assignment(lhs, rhs) => write!(f, "{:?} = {:?}", lhs, rhs),
+            Expr::Assignment(lhs, rhs, _) => write!(f, "(= {} {:?})", lhs.lexeme, rhs),
             Expr::Literal(literal) => write!(f, "{:?}", literal),
-            Expr::Unary(unary_op, expr) => write!(f, "({:?} {:?})", unary_op, expr),
-            Expr::Binary(lhs, binary_op, rhs) => {
+            Expr::Unary(unary_op, expr, _) => write!(f, "({:?} {:?})", unary_op, expr),
+            Expr::Binary(lhs, binary_op, rhs, _) => {
                 write!(f, "({:?} {:?} {:?})", binary_op, lhs, rhs)
             }
             Expr::Grouping(expr) => write!(f, "(group {:?})", expr),
-            Expr::Logical(lhs, op, rhs) => write!(f, "{:?} {:?} {:?}", lhs, op, rhs),
+            Expr::Logical(lhs, op, rhs, _) => write!(f, "({:?} {:?} {:?})", op, lhs, rhs),
+            Expr::Call(callee, args, _) => {
+                write!(f, "(call {:?}", callee)?;
+                for arg in args {
+                    write!(f, " {:?}", arg)?;
+                }
+                write!(f, ")")
+            }
+            Expr::Get(object, name) => write!(f, "(. {:?} {})", object, name.lexeme),
+            Expr::Set(object, name, value) => {
+                write!(f, "(.= {:?} {} {:?})", object, name.lexeme, value)
+            }
+            Expr::This(_, _) => write!(f, "this"),
+            Expr::Super(_, method, _) => write!(f, "super.{}", method.lexeme),
         }
     }
 }
@@ -94,7 +161,7 @@ impl fmt::Debug for Literal {
             Literal::True => write!(f, "true"),
             Literal::False => write!(f, "false"),
             Literal::Nil => write!(f, "nil"),
-            Literal::Identifier(id) => write!(f, "{}", id),
+            Literal::Identifier(id, _) => write!(f, "{}", id.lexeme),
         }
     }
 }
@@ -108,6 +175,15 @@ impl fmt::Debug for UnaryOp {
     }
 }
 
+impl fmt::Debug for LogicalOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogicalOp::Or => write!(f, "or"),
+            LogicalOp::And => write!(f, "and"),
+        }
+    }
+}
+
 impl fmt::Debug for BinaryOp {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -125,6 +201,6 @@ impl fmt::Debug for BinaryOp {
     }
 }
 
-pub fn binop(lhs: Expr, binop: BinaryOp, rhs: Expr) -> Expr {
-    Expr::Binary(Box::new(lhs), binop, Box::new(rhs))
+pub fn binop(lhs: Expr, binop: BinaryOp, rhs: Expr, line: usize) -> Expr {
+    Expr::Binary(Box::new(lhs), binop, Box::new(rhs), line)
 }
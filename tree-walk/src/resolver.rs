@@ -0,0 +1,416 @@
+//! A pass between parsing and evaluation that works out which
+//! declaration each variable refers to, and reports the mistakes that
+//! can be found without running anything.
+
+use std::collections::HashMap;
+
+use crate::error::{Result, parse_error};
+use crate::lexer::Token;
+use crate::program::{Class, Declaration, Depth, Expr, Function, Literal, Program, Statement};
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Method,
+    Initializer,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+pub struct Resolver {
+    /// The local scopes, innermost last, mapping each name to whether
+    /// its initializer has been resolved yet.  Globals aren't tracked.
+    scopes: Vec<HashMap<String, bool>>,
+    function: FunctionType,
+    class: ClassType,
+    errors: Vec<String>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Resolver {
+            scopes: Vec::new(),
+            function: FunctionType::None,
+            class: ClassType::None,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Record the depth of every variable in `program`, or return all
+    /// the errors found.
+    pub fn resolve(mut self, program: &Program) -> Result<()> {
+        for decl in program {
+            self.declaration(decl);
+        }
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(parse_error::<()>(&self.errors.join("\n")))
+        }
+    }
+
+    fn error(&mut self, line: usize, name: &str, msg: &str) {
+        self.errors
+            .push(format!("[line {}] Error at '{}': {}", line, name, msg));
+    }
+
+    fn declaration(&mut self, decl: &Declaration) {
+        match decl {
+            Declaration::Variable {
+                identifier,
+                line,
+                value,
+            } => {
+                self.declare(identifier, *line);
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+                self.define(identifier);
+            }
+            Declaration::Function(function) => {
+                // defined straight away, so the function can call itself
+                self.declare(&function.name, function.line);
+                self.define(&function.name);
+                self.function(function, FunctionType::Function);
+            }
+            Declaration::Class(class) => self.class(class),
+            Declaration::Statement(statement) => self.statement(statement),
+        }
+    }
+
+    fn class(&mut self, class: &Class) {
+        let enclosing = self.class;
+        self.class = ClassType::Class;
+        self.declare(&class.name, class.line);
+        self.define(&class.name);
+
+        if let Some(superclass) = &class.superclass {
+            if let Expr::Literal(Literal::Identifier(name, _)) = superclass
+                && name.lexeme == class.name
+            {
+                self.error(
+                    name.line,
+                    &name.lexeme,
+                    "A class can't inherit from itself.",
+                );
+            }
+            self.class = ClassType::Subclass;
+            self.expr(superclass);
+            // the evaluator binds `super` in a scope around the methods
+            self.scopes
+                .push(HashMap::from([("super".to_string(), true)]));
+        }
+
+        // and `this` in a scope around each method
+        self.scopes
+            .push(HashMap::from([("this".to_string(), true)]));
+        for method in &class.methods {
+            let kind = if method.initializer {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.function(method, kind);
+        }
+        self.scopes.pop();
+
+        if class.superclass.is_some() {
+            self.scopes.pop();
+        }
+        self.class = enclosing;
+    }
+
+    fn function(&mut self, function: &Function, kind: FunctionType) {
+        let enclosing = self.function;
+        self.function = kind;
+        self.scopes.push(HashMap::new());
+        for param in &function.params {
+            self.declare(param, function.line);
+            self.define(param);
+        }
+        for decl in &function.body {
+            self.declaration(decl);
+        }
+        self.scopes.pop();
+        self.function = enclosing;
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Expr(expr) | Statement::Print(expr, _) => self.expr(expr),
+            Statement::Block(decls) => {
+                self.scopes.push(HashMap::new());
+                for decl in decls {
+                    self.declaration(decl);
+                }
+                self.scopes.pop();
+            }
+            Statement::IfElse(cond, then_branch, else_branch, _) => {
+                self.expr(cond);
+                self.statement(then_branch);
+                if let Some(else_branch) = &**else_branch {
+                    self.statement(else_branch);
+                }
+            }
+            Statement::While(cond, body, _) => {
+                self.expr(cond);
+                self.statement(body);
+            }
+            Statement::For(initializer, condition, increment, body, _) => {
+                // the evaluator gives a for loop a scope of its own
+                self.scopes.push(HashMap::new());
+                if let Some(init) = initializer {
+                    self.declaration(init);
+                }
+                if let Some(cond) = condition {
+                    self.expr(cond);
+                }
+                if let Some(inc) = increment {
+                    self.expr(inc);
+                }
+                self.statement(body);
+                self.scopes.pop();
+            }
+            Statement::Return(keyword, value) => {
+                if self.function == FunctionType::None {
+                    self.error(
+                        keyword.line,
+                        &keyword.lexeme,
+                        "Can't return from top-level code.",
+                    );
+                }
+                if value.is_some() && self.function == FunctionType::Initializer {
+                    self.error(
+                        keyword.line,
+                        &keyword.lexeme,
+                        "Can't return a value from an initializer.",
+                    );
+                }
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Binary(lhs, _, rhs, _) | Expr::Logical(lhs, _, rhs, _) => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            Expr::Unary(_, expr, _) | Expr::Grouping(expr) => self.expr(expr),
+            Expr::Assignment(name, value, depth) => {
+                self.expr(value);
+                self.local(name, depth);
+            }
+            Expr::Call(callee, args, _) => {
+                self.expr(callee);
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            Expr::Literal(Literal::Identifier(name, depth)) => {
+                let initializing =
+                    self.scopes.last().and_then(|scope| scope.get(&name.lexeme)) == Some(&false);
+                if initializing {
+                    self.error(
+                        name.line,
+                        &name.lexeme,
+                        "Can't read local variable in its own initializer.",
+                    );
+                }
+                self.local(name, depth);
+            }
+            Expr::Literal(_) => {}
+            Expr::Get(object, _) => self.expr(object),
+            Expr::Set(object, _, value) => {
+                self.expr(value);
+                self.expr(object);
+            }
+            Expr::This(keyword, depth) => {
+                if self.class == ClassType::None {
+                    self.error(
+                        keyword.line,
+                        &keyword.lexeme,
+                        "Can't use 'this' outside of a class.",
+                    );
+                }
+                self.local(keyword, depth);
+            }
+            Expr::Super(keyword, _, depth) => match self.class {
+                ClassType::None => self.error(
+                    keyword.line,
+                    &keyword.lexeme,
+                    "Can't use 'super' outside of a class.",
+                ),
+                ClassType::Class => self.error(
+                    keyword.line,
+                    &keyword.lexeme,
+                    "Can't use 'super' in a class with no superclass.",
+                ),
+                ClassType::Subclass => self.local(keyword, depth),
+            },
+        }
+    }
+
+    fn declare(&mut self, name: &str, line: usize) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        if scope.insert(name.to_string(), false).is_some() {
+            self.error(
+                line,
+                name,
+                "Already a variable with this name in this scope.",
+            );
+        }
+    }
+
+    fn define(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), true);
+        }
+    }
+
+    /// Record how far out `name` was declared, leaving it a global if
+    /// no local scope has it.
+    fn local(&self, name: &Token, depth: &Depth) {
+        let found = self
+            .scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(&name.lexeme));
+        depth.set(found);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn parse(source: &str) -> Program {
+        let (tokens, _) = Lexer::from_source(source.to_string()).tokens();
+        Parser::from_tokens(tokens).parse().unwrap()
+    }
+
+    /// The errors resolving `source` reports.
+    fn errors(source: &str) -> Vec<String> {
+        match Resolver::new().resolve(&parse(source)) {
+            Ok(()) => panic!("{source} resolved without errors"),
+            Err(e) => e.to_string().lines().map(str::to_string).collect(),
+        }
+    }
+
+    /// The variable each `print` statement in `source` prints, with the
+    /// depth the resolver gave it, in order.
+    fn printed_depths(source: &str) -> Vec<(String, Option<usize>)> {
+        fn walk(decls: &[Declaration], found: &mut Vec<(String, Option<usize>)>) {
+            for decl in decls {
+                match decl {
+                    Declaration::Function(function) => walk(&function.body, found),
+                    Declaration::Statement(statement) => statement_depths(statement, found),
+                    _ => {}
+                }
+            }
+        }
+        fn statement_depths(statement: &Statement, found: &mut Vec<(String, Option<usize>)>) {
+            match statement {
+                Statement::Print(Expr::Literal(Literal::Identifier(name, depth)), _) => {
+                    found.push((name.lexeme.clone(), depth.get()))
+                }
+                Statement::Block(decls) => walk(decls, found),
+                _ => {}
+            }
+        }
+
+        let program = parse(source);
+        Resolver::new().resolve(&program).unwrap();
+        let mut found = Vec::new();
+        walk(&program, &mut found);
+        found
+    }
+
+    #[test]
+    fn records_how_far_out_each_variable_was_declared() {
+        let depths = printed_depths(
+            "var a = 1;
+            {
+              var b = 2;
+              fun f(c) {
+                print c;
+                {
+                  print c;
+                  print b;
+                  print a;
+                }
+              }
+              print b;
+            }",
+        );
+        assert_eq!(
+            depths,
+            [
+                ("c".to_string(), Some(0)),
+                ("c".to_string(), Some(1)),
+                ("b".to_string(), Some(2)),
+                ("a".to_string(), None),
+                ("b".to_string(), Some(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_local_cannot_be_read_in_its_own_initializer() {
+        assert_eq!(
+            errors("{\n  var a = a;\n}"),
+            ["[line 2] Error at 'a': Can't read local variable in its own initializer."]
+        );
+        // globals are looked up when the initializer runs instead
+        Resolver::new().resolve(&parse("var a = a;")).unwrap();
+    }
+
+    #[test]
+    fn a_local_cannot_be_declared_twice_in_one_scope() {
+        assert_eq!(
+            errors("{\n  var a = 1;\n  var a = 2;\n}"),
+            ["[line 3] Error at 'a': Already a variable with this name in this scope."]
+        );
+        Resolver::new()
+            .resolve(&parse(
+                "var a = 1; var a = 2; { var a = 3; { var a = 4; } }",
+            ))
+            .unwrap();
+    }
+
+    #[test]
+    fn return_is_only_allowed_in_functions() {
+        assert_eq!(
+            errors("print 1;\nreturn 2;"),
+            ["[line 2] Error at 'return': Can't return from top-level code."]
+        );
+        Resolver::new()
+            .resolve(&parse("fun f() { return 2; }"))
+            .unwrap();
+    }
+
+    #[test]
+    fn reports_every_error() {
+        assert_eq!(
+            errors("return;\n{\n  var a = a;\n}\nprint this;"),
+            [
+                "[line 1] Error at 'return': Can't return from top-level code.",
+                "[line 3] Error at 'a': Can't read local variable in its own initializer.",
+                "[line 5] Error at 'this': Can't use 'this' outside of a class.",
+            ]
+        );
+    }
+}
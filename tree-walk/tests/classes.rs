@@ -0,0 +1,81 @@
+mod common;
+
+use common::lox;
+
+#[test]
+fn instances_hold_fields_and_print_their_class() {
+    let run = lox("
+class Bag {}
+var bag = Bag();
+bag.item = \"apple\";
+print bag.item;
+print bag;
+print Bag;
+");
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(run.stdout, "apple\nBag instance\nBag\n");
+}
+
+#[test]
+fn methods_see_their_instance_as_this() {
+    let run = lox("
+class Counter {
+  increment() {
+    this.count = this.count + 1;
+    return this;
+  }
+}
+var counter = Counter();
+counter.count = 0;
+print counter.increment().increment().count;
+var method = counter.increment;
+method();
+print counter.count;
+");
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(run.stdout, "2\n3\n");
+}
+
+#[test]
+fn init_sets_up_the_instance_and_returns_it() {
+    let run = lox("
+class Point {
+  init(x, y) {
+    this.x = x;
+    this.y = y;
+    return;
+  }
+}
+var p = Point(1, 2);
+print p.x + p.y;
+print p.init(3, 4) == p;
+print p.x;
+");
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(run.stdout, "3\ntrue\n3\n");
+}
+
+#[test]
+fn init_checks_its_arguments() {
+    let run = lox("class Point { init(x, y) {} }\nPoint(1);");
+    assert_eq!(run.code, Some(70));
+    assert_eq!(run.stderr, "Expected 2 arguments but got 1.\n[line 2]\n");
+}
+
+#[test]
+fn this_and_returning_values_are_checked_before_running() {
+    let run = lox("print 1;\nprint this;");
+    assert_eq!(run.code, Some(65));
+    assert_eq!(run.stdout, "");
+    assert_eq!(
+        run.stderr,
+        "[line 2] Error at 'this': Can't use 'this' outside of a class.\n"
+    );
+
+    let run = lox("class A {\n  init() { return 1; }\n}");
+    assert_eq!(run.code, Some(65));
+    assert_eq!(
+        run.stderr,
+        "[line 2] Error at 'return': Can't return a value from an initializer.\n"
+    );
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use common::lox;
+
+#[test]
+fn each_closure_keeps_its_own_state() {
+    let run = lox("
+fun makeCounter() {
+  var i = 0;
+  fun count() {
+    i = i + 1;
+    return i;
+  }
+  return count;
+}
+var a = makeCounter();
+var b = makeCounter();
+print a();
+print a();
+print b();
+print a();
+");
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(run.stdout, "1\n2\n1\n3\n");
+}
+
+#[test]
+fn closures_share_the_variable_they_capture() {
+    let run = lox("
+var get;
+var set;
+{
+  var x = \"before\";
+  fun g() { return x; }
+  fun s(value) { x = value; }
+  get = g;
+  set = s;
+}
+print get();
+set(\"after\");
+print get();
+");
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(run.stdout, "before\nafter\n");
+}
+
+#[test]
+fn closures_see_the_scope_they_were_declared_in() {
+    let run = lox("
+var a = \"global\";
+{
+  fun show() { print a; }
+  show();
+  var a = \"block\";
+  show();
+}
+");
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(run.stdout, "global\nglobal\n");
+}
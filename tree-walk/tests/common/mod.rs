@@ -0,0 +1,48 @@
+// Each test binary compiles its own copy and uses only part of it.
+#![allow(dead_code)]
+
+use std::fs;
+use std::process::Command;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+/// What a run of the interpreter printed and how it exited.
+pub struct Run {
+    pub stdout: String,
+    pub stderr: String,
+    pub code: Option<i32>,
+}
+
+impl Run {
+    pub fn ok(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+/// Run `source` as a script with the given command line flags.
+pub fn lox_with(flags: &[&str], source: &str) -> Run {
+    static SCRIPTS: AtomicUsize = AtomicUsize::new(0);
+    let name = format!(
+        "lox1-{}-{}.lox",
+        std::process::id(),
+        SCRIPTS.fetch_add(1, Ordering::Relaxed)
+    );
+    let path = std::env::temp_dir().join(name);
+    fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_lox1"))
+        .args(flags)
+        .arg(&path)
+        .output()
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+    Run {
+        stdout: String::from_utf8(output.stdout).unwrap(),
+        stderr: String::from_utf8(output.stderr).unwrap(),
+        code: output.status.code(),
+    }
+}
+
+/// Run `source` as a script with the default options.
+pub fn lox(source: &str) -> Run {
+    lox_with(&[], source)
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use common::lox;
+
+/// The runtime error `source` stops with, as printed.
+fn runtime_error(source: &str) -> String {
+    let run = lox(source);
+    assert_eq!(run.code, Some(70), "{source} didn't fail at runtime");
+    run.stderr
+}
+
+#[test]
+fn operators_check_their_operands() {
+    assert_eq!(
+        runtime_error("print 1 + nil;"),
+        "Operands must be two numbers or two strings.\n[line 1]\n"
+    );
+    assert_eq!(
+        runtime_error("print \"a\" + 1;"),
+        "Operands must be two numbers or two strings.\n[line 1]\n"
+    );
+    for op in ["-", "*", "/", "<", "<=", ">", ">="] {
+        assert_eq!(
+            runtime_error(&format!("print 1 {op} \"2\";")),
+            "Operands must be numbers.\n[line 1]\n",
+            "{op}"
+        );
+    }
+    assert_eq!(
+        runtime_error("print \"a\" < \"b\";"),
+        "Operands must be numbers.\n[line 1]\n"
+    );
+    assert_eq!(
+        runtime_error("print -nil;"),
+        "Operand must be a number.\n[line 1]\n"
+    );
+}
+
+#[test]
+fn comparisons_with_nan_are_false() {
+    let run = lox("var nan = 0 / 0;\nprint nan < 1;\nprint nan >= nan;\nprint nan == nan;");
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(run.stdout, "false\nfalse\nfalse\n");
+}
+
+#[test]
+fn unknown_variables_are_errors() {
+    assert_eq!(
+        runtime_error("print notDefined;"),
+        "Undefined variable 'notDefined'.\n[line 1]\n"
+    );
+    assert_eq!(
+        runtime_error("notDefined = 1;"),
+        "Undefined variable 'notDefined'.\n[line 1]\n"
+    );
+}
+
+#[test]
+fn errors_propagate_out_of_calls_and_stop_the_program() {
+    let run = lox("
+fun inner() {
+  return -\"x\";
+}
+fun outer() {
+  print \"before\";
+  var value = inner();
+  print \"not reached\";
+  return value;
+}
+print outer() + 1;
+print \"not reached either\";
+");
+    assert_eq!(run.code, Some(70));
+    assert_eq!(run.stdout, "before\n");
+    assert_eq!(run.stderr, "Operand must be a number.\n[line 3]\n");
+}
+
+#[test]
+fn errors_propagate_out_of_blocks_loops_and_arguments() {
+    let run = lox("
+fun f(a, b) { return a; }
+for (var i = 0; i < 3; i = i + 1) {
+  {
+    print i;
+    if (i == 1) f(1, i * nil);
+  }
+}
+");
+    assert_eq!(run.code, Some(70));
+    assert_eq!(run.stdout, "0\n1\n");
+    assert_eq!(run.stderr, "Operands must be numbers.\n[line 6]\n");
+}
+
+#[test]
+fn lexer_and_parser_errors_are_reported_in_line_order() {
+    let run = lox("var a = 1\nprint @;\nvar = 2;\nprint \"oops;\n");
+    assert_eq!(run.code, Some(65));
+    assert_eq!(run.stdout, "");
+    assert_eq!(
+        run.stderr,
+        "[line 2] Error: Unexpected character.\n\
+         [line 2] Error at 'print': Expect ';' after variable declaration.\n\
+         [line 3] Error at '=': Expect variable name.\n\
+         [line 4] Error: Unterminated string.\n\
+         [line 4] Error at end: Expect expression.\n"
+    );
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use common::lox;
+
+#[test]
+fn unbounded_recursion_is_a_runtime_error() {
+    let run = lox("fun f() {\n  f();\n}\nf();\n");
+    assert_eq!(run.code, Some(70));
+    assert_eq!(run.stderr, "Stack overflow.\n[line 2]\n");
+}
+
+#[test]
+fn recursion_below_the_cap_still_works() {
+    let run =
+        lox("fun count(n) { if (n == 0) return 0; return 1 + count(n - 1); }\nprint count(200);");
+    assert!(run.ok());
+    assert_eq!(run.stdout, "200\n");
+}
+
+#[test]
+fn calls_check_the_number_of_arguments() {
+    let run = lox("fun add(a, b) { return a + b; }\nprint add(1);");
+    assert_eq!(run.code, Some(70));
+    assert_eq!(run.stderr, "Expected 2 arguments but got 1.\n[line 2]\n");
+
+    let run = lox("fun none() {}\nnone(1, 2, 3);");
+    assert_eq!(run.code, Some(70));
+    assert_eq!(run.stderr, "Expected 0 arguments but got 3.\n[line 2]\n");
+}
+
+#[test]
+fn only_functions_and_classes_can_be_called() {
+    for callee in ["nil", "true", "1", "\"text\""] {
+        let run = lox(&format!("print 1;\n{callee}();"));
+        assert_eq!(run.code, Some(70), "{callee}");
+        assert_eq!(run.stdout, "1\n");
+        assert_eq!(
+            run.stderr, "Can only call functions and classes.\n[line 2]\n",
+            "{callee}"
+        );
+    }
+}
+
+#[test]
+fn return_leaves_loops_and_blocks() {
+    let run = lox("
+fun fromWhile() {
+  var i = 0;
+  while (true) {
+    if (i == 3) return i;
+    i = i + 1;
+  }
+  print \"not reached\";
+}
+fun fromFor() {
+  for (var i = 0; i < 10; i = i + 1) {
+    for (;;) {
+      return \"for \" + \"done\";
+    }
+  }
+  print \"not reached\";
+}
+fun fromBlock() {
+  {
+    var a = \"block\";
+    {
+      return a;
+    }
+  }
+  print \"not reached\";
+}
+fun bare() {
+  return;
+  print \"not reached\";
+}
+print fromWhile();
+print fromFor();
+print fromBlock();
+print bare();
+");
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(run.stdout, "3\nfor done\nblock\nnil\n");
+}
+
+#[test]
+fn returning_restores_the_callers_scope() {
+    let run = lox("
+var a = \"global\";
+fun f() {
+  var a = \"local\";
+  while (true) { return a; }
+}
+{
+  var a = \"block\";
+  print f();
+  print a;
+}
+print a;
+");
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(run.stdout, "local\nblock\nglobal\n");
+}
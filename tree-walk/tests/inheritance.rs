@@ -0,0 +1,75 @@
+mod common;
+
+use common::lox;
+
+#[test]
+fn subclasses_inherit_and_override_methods() {
+    let run = lox("
+class A {
+  name() { return \"A\"; }
+  greet() { print \"hello from \" + this.name(); }
+}
+class B < A {
+  name() { return \"B\"; }
+}
+A().greet();
+B().greet();
+");
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(run.stdout, "hello from A\nhello from B\n");
+}
+
+#[test]
+fn super_calls_the_superclass_method_on_this() {
+    let run = lox("
+class Doughnut {
+  init(filling) { this.filling = filling; }
+  cook() { print \"Fry until golden brown.\"; }
+}
+class BostonCream < Doughnut {
+  init() { super.init(\"custard\"); }
+  cook() {
+    super.cook();
+    print \"Pipe full of \" + this.filling + \".\";
+  }
+}
+BostonCream().cook();
+");
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(
+        run.stdout,
+        "Fry until golden brown.\nPipe full of custard.\n"
+    );
+}
+
+#[test]
+fn super_starts_from_the_class_the_method_is_in() {
+    let run = lox("
+class A { method() { print \"A\"; } }
+class B < A {
+  method() { print \"B\"; }
+  test() { super.method(); }
+}
+class C < B {}
+C().test();
+");
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(run.stdout, "A\n");
+}
+
+#[test]
+fn superclass_must_be_a_class() {
+    let run = lox("var NotAClass = \"so not a class\";\nclass Sub < NotAClass {}");
+    assert_eq!(run.code, Some(70));
+    assert_eq!(run.stderr, "Superclass must be a class.\n[line 2]\n");
+}
+
+#[test]
+fn super_needs_a_superclass() {
+    let run = lox("class A {\n  m() { super.m(); }\n}");
+    assert_eq!(run.code, Some(65));
+    assert_eq!(
+        run.stderr,
+        "[line 2] Error at 'super': Can't use 'super' in a class with no superclass.\n"
+    );
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use common::lox;
+use common::lox_with;
+
+/// Declares `touch`, which prints its argument and returns it, so a
+/// test can see which operands were evaluated.
+const TOUCH: &str = "fun touch(value) { print value; return value; }\n";
+
+#[test]
+fn and_skips_its_right_operand_when_the_left_is_false() {
+    let run = lox(&format!("{TOUCH}print false and touch(true);"));
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(run.stdout, "false\n");
+
+    let run = lox(&format!("{TOUCH}print true and touch(false);"));
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(run.stdout, "false\nfalse\n");
+}
+
+#[test]
+fn or_skips_its_right_operand_when_the_left_is_true() {
+    let run = lox(&format!("{TOUCH}print true or touch(false);"));
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(run.stdout, "true\n");
+
+    let run = lox(&format!("{TOUCH}print false or touch(true);"));
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(run.stdout, "true\ntrue\n");
+}
+
+#[test]
+fn skipped_operands_are_not_checked() {
+    // the right operands would fail if they were evaluated
+    let run = lox("print false and 1 + nil;\nprint true or undefined;");
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(run.stdout, "false\ntrue\n");
+}
+
+#[test]
+fn chains_stop_at_the_first_deciding_operand() {
+    let run = lox(&format!(
+        "{TOUCH}print touch(true) and touch(false) and touch(true);\n\
+         print touch(false) or touch(true) or touch(false);"
+    ));
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(run.stdout, "true\nfalse\nfalse\nfalse\ntrue\ntrue\n");
+}
+
+#[test]
+fn reference_mode_returns_the_deciding_operand() {
+    let run = lox_with(
+        &["--reference"],
+        "print 1 and 2;\n\
+         print nil and 2;\n\
+         print false and 2;\n\
+         print 1 or 2;\n\
+         print nil or \"default\";\n\
+         print false or nil;\n\
+         print 0 and \"\";",
+    );
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(run.stdout, "2\nnil\nfalse\n1\ndefault\nnil\n\n");
+}
+
+#[test]
+fn reference_mode_still_short_circuits() {
+    let run = lox_with(
+        &["--reference"],
+        &format!("{TOUCH}print nil and touch(1);\nprint \"hi\" or touch(2);"),
+    );
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(run.stdout, "nil\nhi\n");
+}
@@ -0,0 +1,17 @@
+mod common;
+
+use common::lox;
+
+#[test]
+fn print_writes_only_the_value() {
+    let run = lox("print 1 + 1; print \"a\" + \"b\"; 3 * 4; print nil; print 0.5;");
+    assert!(run.ok());
+    assert_eq!(run.stdout, "2\nab\nnil\n0.5\n");
+}
+
+#[test]
+fn runtime_errors_stop_the_program() {
+    let run = lox("print 1; print 1 + nil; print 2;");
+    assert_eq!(run.code, Some(70));
+    assert_eq!(run.stdout, "1\n");
+}
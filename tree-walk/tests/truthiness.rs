@@ -0,0 +1,119 @@
+mod common;
+
+use common::lox;
+use common::lox_with;
+
+/// Values that aren't bools, as Lox source.
+const NON_BOOLS: [&str; 5] = ["nil", "0", "1", "\"\"", "\"text\""];
+
+/// The runtime error `source` stops with in strict mode, as printed.
+fn strict_error(source: &str) -> String {
+    let run = lox(source);
+    assert_eq!(run.code, Some(70), "{source} didn't fail at runtime");
+    assert_eq!(run.stdout, "", "{source} ran its body");
+    run.stderr
+}
+
+#[test]
+fn strict_conditions_must_be_bools() {
+    for value in NON_BOOLS {
+        assert_eq!(
+            strict_error(&format!("if ({value}) print 1;")),
+            "Condition must be a boolean.\n[line 1]\n",
+            "{value}"
+        );
+        assert_eq!(
+            strict_error(&format!("while ({value}) print 1;")),
+            "Condition must be a boolean.\n[line 1]\n",
+            "{value}"
+        );
+        assert_eq!(
+            strict_error(&format!("for (; {value};) print 1;")),
+            "Condition must be a boolean.\n[line 1]\n",
+            "{value}"
+        );
+    }
+}
+
+#[test]
+fn strict_operators_need_bools() {
+    for value in NON_BOOLS {
+        assert_eq!(
+            strict_error(&format!("print !{value};")),
+            "Condition must be a boolean.\n[line 1]\n",
+            "{value}"
+        );
+        for source in [
+            format!("print {value} and true;"),
+            format!("print {value} or true;"),
+            format!("print true and {value};"),
+            format!("print false or {value};"),
+        ] {
+            assert_eq!(
+                strict_error(&source),
+                "Condition must be a boolean.\n[line 1]\n",
+                "{source}"
+            );
+        }
+    }
+}
+
+#[test]
+fn strict_mode_accepts_bools() {
+    let run = lox("
+if (true) print \"if\";
+var i = 0;
+while (i < 1) { print \"while\"; i = i + 1; }
+for (var j = 0; j < 1; j = j + 1) print \"for\";
+print !false;
+print true and false or true;
+");
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(run.stdout, "if\nwhile\nfor\ntrue\ntrue\n");
+}
+
+#[test]
+fn reference_mode_treats_only_nil_and_false_as_false() {
+    let run = lox_with(
+        &["--reference"],
+        "
+fun f() {}
+class C {}
+fun truth(value) {
+  if (value) return \"true\";
+  return \"false\";
+}
+print truth(nil);
+print truth(false);
+print truth(true);
+print truth(0);
+print truth(\"\");
+print truth(\"false\");
+print truth(f);
+print truth(C);
+print truth(C());
+",
+    );
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(
+        run.stdout,
+        "false\nfalse\ntrue\ntrue\ntrue\ntrue\ntrue\ntrue\ntrue\n"
+    );
+}
+
+#[test]
+fn reference_mode_uses_truthiness_in_every_condition() {
+    let run = lox_with(
+        &["--reference"],
+        "
+if (0) print \"if\";
+var n = 2;
+while (n) { print \"while\"; if (n == 1) n = nil; else n = n - 1; }
+for (var s = \"once\"; s; s = nil) print s;
+print !nil;
+print !0;
+",
+    );
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(run.stdout, "if\nwhile\nwhile\nonce\ntrue\nfalse\n");
+}
@@ -0,0 +1,24 @@
+mod common;
+
+use common::lox;
+
+#[test]
+fn reading_an_uninitialized_global_gives_nil() {
+    let run = lox("var a; print a;");
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(run.stdout, "nil\n");
+}
+
+#[test]
+fn reading_an_uninitialized_local_gives_nil() {
+    let run = lox("{\n  var b;\n  print b;\n}");
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(run.stdout, "nil\n");
+}
+
+#[test]
+fn globals_can_be_redeclared() {
+    let run = lox("var a = 1;\nvar a;\nprint a;\nvar a = 2;\nprint a;");
+    assert!(run.ok(), "{}", run.stderr);
+    assert_eq!(run.stdout, "nil\n2\n");
+}